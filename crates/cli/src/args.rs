@@ -1,14 +1,43 @@
+use libpkgx::shell::Shell;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Mode {
     X,
     Help,
     Version,
+    Query,
+    Info,
+    Tree,
+    List,
+    Sync,
+    Deactivate,
+    Shellcode,
+    Hook,
 }
 
+#[derive(Debug, Default)]
 pub struct Flags {
     pub silent: bool,
     pub json: bool,
+    /// `--json=stream`, see `stream`
+    pub stream: bool,
+    pub dev: bool,
+    pub pure: bool,
+    pub explain: bool,
+    /// `-v` is debug logging, `-vv` trace
+    pub verbose: u8,
+    pub spawn: bool,
+    pub sandbox: bool,
+    /// no network in the sandbox
+    pub offline: bool,
+    /// extra paths the sandbox can see
+    pub allow: Vec<PathBuf>,
+    pub shell: Option<Shell>,
 }
 
+#[derive(Debug)]
 pub struct Args {
     pub plus: Vec<String>,
     pub args: Vec<String>,
@@ -17,52 +46,358 @@ pub struct Args {
     pub flags: Flags,
 }
 
-pub fn parse() -> Args {
-    let mut mode = Mode::X;
-    let mut plus = Vec::new();
-    let mut args = Vec::new();
-    let mut silent: bool = false;
-    let mut json: bool = false;
-    let mut find_program = false;
+/// reported with exit code 2
+#[derive(Debug, PartialEq)]
+pub struct ArgsError(pub String);
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Opt {
+    Mode(Mode),
+    Silent,
+    Json,
+    Dev,
+    Pure,
+    Explain,
+    Verbose,
+    Spawn,
+    Sandbox,
+    Allow,
+    Shell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    None,
+    Required(&'static str),
+    Optional(&'static str),
+}
+
+/// An option we accept, `help::usage` is generated from these.
+pub struct Spec {
+    pub long: &'static str,
+    pub short: Option<char>,
+    pub value: Value,
+    /// empty for options we don’t advertise
+    pub help: &'static str,
+    opt: Opt,
+}
+
+const fn spec(
+    long: &'static str,
+    short: Option<char>,
+    value: Value,
+    help: &'static str,
+    opt: Opt,
+) -> Spec {
+    Spec {
+        long,
+        short,
+        value,
+        help,
+        opt,
+    }
+}
+
+pub const MODES: &[Spec] = &[
+    spec(
+        "query",
+        Some('Q'),
+        Value::None,
+        "which pkgs provide these programs",
+        Opt::Mode(Mode::Query),
+    ),
+    spec(
+        "info",
+        None,
+        Value::None,
+        "programs, deps, etc. of these pkgs",
+        Opt::Mode(Mode::Info),
+    ),
+    spec(
+        "tree",
+        None,
+        Value::None,
+        "dependency tree of these pkgs",
+        Opt::Mode(Mode::Tree),
+    ),
+    spec(
+        "list",
+        None,
+        Value::None,
+        "installed pkgs",
+        Opt::Mode(Mode::List),
+    ),
+    spec(
+        "sync",
+        None,
+        Value::None,
+        "update the pkg-db",
+        Opt::Mode(Mode::Sync),
+    ),
+    spec(
+        "deactivate",
+        None,
+        Value::None,
+        "undo the env output",
+        Opt::Mode(Mode::Deactivate),
+    ),
+    spec(
+        "shellcode",
+        None,
+        Value::None,
+        "shell hook that runs `--dev` as you `cd`",
+        Opt::Mode(Mode::Shellcode),
+    ),
+    // run by the `--shellcode` on `cd`
+    spec("hook", None, Value::None, "", Opt::Mode(Mode::Hook)),
+    spec(
+        "help",
+        Some('h'),
+        Value::None,
+        "this message",
+        Opt::Mode(Mode::Help),
+    ),
+    spec(
+        "version",
+        None,
+        Value::None,
+        "print the version",
+        Opt::Mode(Mode::Version),
+    ),
+];
+
+pub const FLAGS: &[Spec] = &[
+    spec(
+        "silent",
+        Some('s'),
+        Value::None,
+        "no chat. no errors. just execute.",
+        Opt::Silent,
+    ),
+    spec(
+        "json",
+        Some('j'),
+        Value::Optional("stream"),
+        "output JSON, `=stream` for events as they happen",
+        Opt::Json,
+    ),
+    spec(
+        "dev",
+        None,
+        Value::None,
+        "add the deps from the nearest pkgx.yaml",
+        Opt::Dev,
+    ),
+    spec(
+        "pure",
+        None,
+        Value::None,
+        "don’t inherit the environment (or system PATH)",
+        Opt::Pure,
+    ),
+    spec(
+        "spawn",
+        None,
+        Value::None,
+        "run as a child process rather than replacing pkgx",
+        Opt::Spawn,
+    ),
+    spec(
+        "sandbox",
+        None,
+        Value::Optional("offline"),
+        "only let the cmd see its pkgs and the current directory",
+        Opt::Sandbox,
+    ),
+    spec(
+        "allow",
+        None,
+        Value::Required("<dir>"),
+        "something else the --sandbox can see",
+        Opt::Allow,
+    ),
+    spec(
+        "shell",
+        None,
+        Value::Required("<sh>"),
+        "env output for bash, zsh, fish, elvish or nu",
+        Opt::Shell,
+    ),
+    spec(
+        "explain",
+        None,
+        Value::None,
+        "show where each env var’s values come from",
+        Opt::Explain,
+    ),
+    spec(
+        "verbose",
+        Some('v'),
+        Value::None,
+        "log what pkgx is doing, `-vv` for more",
+        Opt::Verbose,
+    ),
+];
+
+pub fn parse() -> Result<Args, ArgsError> {
+    parse_from(std::env::args().skip(1))
+}
+
+pub fn parse_from<I>(input: I) -> Result<Args, ArgsError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut rv = Args {
+        plus: Vec::new(),
+        args: Vec::new(),
+        find_program: false,
+        mode: Mode::X,
+        flags: Flags::default(),
+    };
+    // how the mode was spelt, for errors
+    let mut mode_arg: Option<String> = None;
     let mut collecting_args = false;
 
-    for arg in std::env::args().skip(1) {
+    for arg in input {
         if collecting_args {
-            args.push(arg);
-        } else if arg.starts_with('+') {
-            plus.push(arg.trim_start_matches('+').to_string());
+            rv.args.push(arg);
+        } else if let Some(pkgspec) = arg.strip_prefix('+') {
+            rv.plus.push(pkgspec.to_string());
         } else if arg == "--" {
-            find_program = false;
+            rv.find_program = false;
             collecting_args = true;
-        } else if arg.starts_with("--") {
-            match arg.as_str() {
-                "--json" => json = true,
-                "--silent" => silent = true,
-                "--help" => mode = Mode::Help,
-                "--version" => mode = Mode::Version,
-                _ => panic!("unknown argument {}", arg),
-            }
-        } else if arg.starts_with('-') {
-            // spit arg into characters
+        } else if let Some(long) = arg.strip_prefix("--") {
+            let (name, value) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            let spec = MODES
+                .iter()
+                .chain(FLAGS)
+                .find(|spec| spec.long == name)
+                .ok_or_else(|| ArgsError(format!("unknown option: --{}", name)))?;
+            apply(spec, value, &arg, &mut rv, &mut mode_arg)?;
+        } else if arg.len() > 1 && arg.starts_with('-') {
+            // grouped short flags, eg. `-sj`
             for c in arg.chars().skip(1) {
-                match c {
-                    's' => silent = true,
-                    'j' => json = true,
-                    _ => panic!("unknown argument: -{}", c),
-                }
+                let spec = MODES
+                    .iter()
+                    .chain(FLAGS)
+                    .find(|spec| spec.short == Some(c))
+                    .ok_or_else(|| ArgsError(format!("unknown option: -{}", c)))?;
+                apply(spec, None, &format!("-{}", c), &mut rv, &mut mode_arg)?;
             }
-        } else {
-            find_program = !arg.contains('/');
+        } else if rv.mode == Mode::X {
+            rv.find_program = !arg.contains('/');
             collecting_args = true;
-            args.push(arg);
+            rv.args.push(arg);
+        } else {
+            // operands for the mode, eg. `pkgx --query node`
+            rv.args.push(arg);
+        }
+    }
+
+    let operands = !rv.args.is_empty() || !rv.plus.is_empty();
+    match rv.mode {
+        Mode::Query if rv.args.is_empty() => {
+            Err(format!("{} needs programs to look up", mode_arg.unwrap()))
+        }
+        Mode::Info | Mode::Tree if !operands => Err(format!("{} needs pkgs", mode_arg.unwrap())),
+        Mode::Version
+        | Mode::List
+        | Mode::Sync
+        | Mode::Deactivate
+        | Mode::Shellcode
+        | Mode::Hook
+            if operands =>
+        {
+            Err(format!("{} takes no arguments", mode_arg.unwrap()))
+        }
+        _ => Ok(()),
+    }
+    .map_err(ArgsError)?;
+
+    Ok(rv)
+}
+
+fn apply(
+    spec: &Spec,
+    value: Option<&str>,
+    arg: &str,
+    rv: &mut Args,
+    mode_arg: &mut Option<String>,
+) -> Result<(), ArgsError> {
+    match (spec.value, value) {
+        (Value::None, Some(_)) => {
+            return Err(ArgsError(format!("--{} doesn’t take a value", spec.long)));
+        }
+        (Value::Required(placeholder), None) => {
+            return Err(ArgsError(format!(
+                "--{} needs a value: --{}={}",
+                spec.long, spec.long, placeholder
+            )));
         }
+        _ => (),
     }
 
-    Args {
-        plus,
-        args,
-        find_program,
-        mode,
-        flags: Flags { silent, json },
+    let flags = &mut rv.flags;
+    match spec.opt {
+        Opt::Mode(mode) => {
+            // `--help` wins since it’s what the user needs
+            if rv.mode == Mode::Help {
+                return Ok(());
+            }
+            let conflicts = rv.mode != mode && mode != Mode::Help;
+            if let Some(previous) = mode_arg.as_ref().filter(|_| conflicts) {
+                return Err(ArgsError(format!(
+                    "{} and {} can’t be used together",
+                    previous, arg
+                )));
+            }
+            if mode == Mode::Hook {
+                flags.dev = true;
+                flags.silent = true;
+            }
+            rv.mode = mode;
+            *mode_arg = Some(arg.to_string());
+        }
+        Opt::Silent => flags.silent = true,
+        Opt::Json => {
+            flags.json = true;
+            match value {
+                None => (),
+                Some("stream") => flags.stream = true,
+                Some(value) => {
+                    return Err(ArgsError(format!("unknown --json mode: {}", value)));
+                }
+            }
+        }
+        Opt::Dev => flags.dev = true,
+        Opt::Pure => flags.pure = true,
+        Opt::Explain => flags.explain = true,
+        Opt::Verbose => flags.verbose = flags.verbose.saturating_add(1),
+        Opt::Spawn => flags.spawn = true,
+        Opt::Sandbox => {
+            flags.sandbox = true;
+            match value {
+                None => (),
+                Some("offline") => flags.offline = true,
+                Some(value) => {
+                    return Err(ArgsError(format!("unknown --sandbox mode: {}", value)));
+                }
+            }
+        }
+        Opt::Allow => flags.allow.push(PathBuf::from(value.unwrap())),
+        Opt::Shell => flags.shell = Some(value.unwrap().parse().map_err(ArgsError)?),
     }
+
+    Ok(())
 }
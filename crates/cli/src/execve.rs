@@ -39,11 +39,7 @@ pub fn execve(
         .collect::<Result<_, _>>()?;
 
     // Replace the process with the new command, arguments, and environment
-    let execve_result = nix_execve(&c_command, &c_args, &c_env);
-    if execve_result.is_err() {
-        let errno = execve_result.unwrap_err();
-        return Err(format!("execve failed with errno: {}", errno).into());
-    }
-
-    Ok(())
+    // on success this never returns
+    let Err(errno) = nix_execve(&c_command, &c_args, &c_env);
+    Err(format!("execve failed with errno: {}", errno).into())
 }
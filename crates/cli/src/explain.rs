@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+
+use libpkgx::env::{Contribution, Source};
+use serde_json::{json, Value};
+
+/// How each variable would change relative to `current`
+fn status(key: &str, current: &HashMap<String, String>) -> &'static str {
+    if current.contains_key(key) {
+        "modified"
+    } else {
+        "added"
+    }
+}
+
+// contributions grouped by variable, keeping the order variables first appear
+fn group(contributions: &[Contribution]) -> Vec<(&str, Vec<&Contribution>)> {
+    let mut rv: Vec<(&str, Vec<&Contribution>)> = vec![];
+    for contribution in contributions {
+        match rv.iter_mut().find(|(key, _)| *key == contribution.key) {
+            Some((_, group)) => group.push(contribution),
+            None => rv.push((&contribution.key, vec![contribution])),
+        }
+    }
+    rv
+}
+
+fn origin(contribution: &Contribution) -> String {
+    let source = match &contribution.source {
+        Source::Suffix(suffix) => suffix.clone(),
+        Source::Default => "default".to_string(),
+        Source::RuntimeEnv => "runtime.env".to_string(),
+    };
+    match &contribution.pkg {
+        Some(pkg) => format!("{} ({})", pkg, source),
+        None => source,
+    }
+}
+
+pub fn table(contributions: &[Contribution], current: &HashMap<String, String>) -> String {
+    let width = contributions
+        .iter()
+        .map(|contribution| contribution.value.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut rv = String::new();
+    for (key, group) in group(contributions) {
+        let marker = if current.contains_key(key) { '~' } else { '+' };
+        rv.push_str(&format!("{} {}\n", marker, key));
+        for contribution in group {
+            rv.push_str(&format!(
+                "    {:width$}  {}\n",
+                contribution.value,
+                origin(contribution),
+                width = width
+            ));
+        }
+    }
+    rv
+}
+
+pub fn json(contributions: &[Contribution], current: &HashMap<String, String>) -> Value {
+    let env: Vec<Value> = group(contributions)
+        .into_iter()
+        .map(|(key, group)| {
+            json!({
+                "key": key,
+                "status": status(key, current),
+                "contributions": group,
+            })
+        })
+        .collect();
+    json!({ "env": env })
+}
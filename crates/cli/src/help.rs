@@ -1,27 +1,55 @@
 use regex::Regex;
 
+use crate::args::{Spec, Value, FLAGS, MODES};
+
 fn dim(input: &str) -> String {
     // Placeholder function for "dim" styling
     format!("\x1b[2m{}\x1b[0m", input)
 }
 
+const SYNOPSIS: &str = "pkgx [+pkg@x.y…] <program|path> [--] [arg…]";
+
 pub fn usage() -> String {
-    let usage = r##"
+    let mut usage = format!(
+        r##"
 usage:
-  pkgx [+pkg@x.y…] <program|path> [--] [arg…]
+  {}
 
 examples:
   $ pkgx gum format "# hello world" "sup?"
   $ pkgx node@18 --eval 'console.log("hello world")'
   $ pkgx +openssl cargo build
+  $ eval "$(pkgx --dev)"
+  $ pkgx --query python3.11
+"##,
+        SYNOPSIS
+    );
 
-flags:
-  -s, --silent  # no chat. no errors. just execute.
-  --version
+    let width = MODES
+        .iter()
+        .chain(FLAGS)
+        .map(|spec| name(spec).chars().count())
+        .max()
+        .unwrap_or(0);
+    for (title, specs) in [("modes", MODES), ("flags", FLAGS)] {
+        usage.push_str(&format!("\n{}:\n", title));
+        for spec in specs.iter().filter(|spec| !spec.help.is_empty()) {
+            let name = name(spec);
+            usage.push_str(&format!(
+                "  {:width$}  # {}\n",
+                name,
+                spec.help,
+                width = width
+            ));
+        }
+    }
 
+    usage.push_str(
+        r##"
 more:
   $ open https://docs.pkgx.sh
-"##;
+"##,
+    );
 
     let usage = usage
         .replace('[', &dim("["))
@@ -38,3 +66,22 @@ more:
     })
     .to_string()
 }
+
+/// for after errors, a reminder rather than the whole usage
+pub fn hint() -> String {
+    format!("usage: {}\nsee: pkgx --help", SYNOPSIS)
+}
+
+// eg. `-s, --silent` or `--shell=<sh>`
+fn name(spec: &Spec) -> String {
+    let mut rv = match spec.short {
+        Some(short) => format!("-{}, --{}", short, spec.long),
+        None => format!("--{}", spec.long),
+    };
+    match spec.value {
+        Value::None => (),
+        Value::Required(placeholder) => rv.push_str(&format!("={}", placeholder)),
+        Value::Optional(value) => rv.push_str(&format!("[={}]", value)),
+    }
+    rv
+}
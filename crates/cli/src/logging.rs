@@ -0,0 +1,36 @@
+use std::io::IsTerminal;
+
+use tracing_subscriber::{fmt::time::uptime, EnvFilter};
+
+/// Logs to stderr at a level from `-v`s, or per `PKGX_DEBUG` which is either
+/// `1` (everything) or a filter like `libpkgx::install=trace`.
+///
+/// Returns whether anything will be logged.
+pub fn init(verbose: u8) -> bool {
+    let debug = std::env::var("PKGX_DEBUG").ok();
+    let Some(directives) = directives(debug.as_deref(), verbose) else {
+        return false;
+    };
+
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new(directives))
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_timer(uptime())
+        .init();
+    true
+}
+
+/// The filter to log with, `None` for no logging. An empty `PKGX_DEBUG` is as
+/// good as unset.
+pub fn directives(debug: Option<&str>, verbose: u8) -> Option<String> {
+    match debug {
+        Some("1") => Some("pkgx=trace,libpkgx=trace".to_string()),
+        Some(value) if !value.is_empty() && value != "0" => Some(value.to_string()),
+        _ => match verbose {
+            0 => None,
+            1 => Some("pkgx=debug,libpkgx=debug".to_string()),
+            _ => Some("pkgx=trace,libpkgx=trace".to_string()),
+        },
+    }
+}
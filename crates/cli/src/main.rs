@@ -1,29 +1,83 @@
 mod args;
 mod execve;
+mod explain;
 mod help;
+mod logging;
+mod progress;
+mod prompt;
+mod query;
+mod stream;
 #[cfg(test)]
 mod tests;
 
-use std::{error::Error, fmt::Write, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    error::Error,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use execve::execve;
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
 use libpkgx::{
-    config::Config, env, hydrate::hydrate, install_multi, pantry_db, resolve::resolve, sync,
-    types::PackageReq, utils,
+    config::Config,
+    env, env_cache,
+    events::{self, Event},
+    hydrate::hydrate,
+    install_multi, manifest,
+    pantry_db::{self, Interpreter},
+    resolve::resolve,
+    run, script,
+    shell::{self, Change, Shell},
+    sync,
+    types::{Installation, PackageReq},
+    user_config::UserConfig,
+    utils,
+    which::{self, which, WhichError},
 };
 use rusqlite::Connection;
 use serde_json::json;
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn Error>> {
+async fn main() {
+    let args = match args::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("pkgx: {}", err);
+            eprintln!("{}", help::hint());
+            std::process::exit(2);
+        }
+    };
+    let (json, stream, silent) = (args.flags.json, args.flags.stream, args.flags.silent);
+
+    if let Err(err) = pkgx(args).await {
+        let (code, status) = error_code(err.as_ref());
+        if stream {
+            let message = err.to_string();
+            stream::emit(json!({ "event": "error", "code": code, "message": message }));
+        }
+        if json {
+            println!(
+                "{}",
+                json!({ "error": { "code": code, "message": err.to_string() } })
+            );
+        } else if !silent {
+            eprintln!("pkgx: {}", err);
+        }
+        std::process::exit(status);
+    }
+}
+
+async fn pkgx(args: args::Args) -> Result<(), Box<dyn Error>> {
     let args::Args {
-        plus,
+        mut plus,
         mut args,
         mode,
         flags,
         find_program,
-    } = args::parse();
+    } = args;
+
+    // the spinner would garble the logs or the stream
+    let logging = logging::init(flags.verbose) || flags.stream;
 
     match mode {
         args::Mode::Help => {
@@ -34,16 +88,56 @@ async fn main() -> Result<(), Box<dyn Error>> {
             println!("pkgx {}", env!("CARGO_PKG_VERSION"));
             return Ok(());
         }
-        args::Mode::X => (),
+        args::Mode::Deactivate => {
+            let shell = flags.shell.unwrap_or_else(Shell::detect);
+            let current = std::env::vars().collect();
+            print!("{}", shell::render(shell, &shell::deactivate(&current)));
+            return Ok(());
+        }
+        args::Mode::Shellcode => {
+            let shell = flags.shell.unwrap_or_else(Shell::detect);
+            print!("{}", shell::hook(shell, &std::env::current_exe()?)?);
+            return Ok(());
+        }
+        _ => (),
     }
 
     let config = Config::new()?;
+    let user_config = UserConfig::load(&config)?;
+
+    if mode == args::Mode::List {
+        return query::list(&config, flags.json).await;
+    }
+
+    // the directory hook (see `--shellcode`) runs often so must be fast
+    let mut hook_key = None;
+    if let args::Mode::Hook = mode {
+        let shell = flags.shell.unwrap_or_else(Shell::detect);
+        let current = std::env::vars().collect::<HashMap<_, _>>();
+        let key = match manifest::find(&std::env::current_dir()?)? {
+            Some(manifest) => env_cache::key(&manifest)?,
+            None => {
+                if current.contains_key(shell::DEV) {
+                    print!("{}", shell::render(shell, &shell::deactivate(&current)));
+                }
+                return Ok(());
+            }
+        };
+        if current.get(shell::DEV) == Some(&key) {
+            return Ok(());
+        }
+        if let Some(changes) = env_cache::get(&key, &config) {
+            print!("{}", hook_code(shell, &current, &changes, &key));
+            return Ok(());
+        }
+        hook_key = Some(key);
+    }
 
     let cache_dir = config.pantry_dir.parent().unwrap();
     std::fs::create_dir_all(cache_dir)?;
     let mut conn = Connection::open(cache_dir.join("pantry.db"))?;
 
-    let spinner = if flags.silent {
+    let spinner = if flags.silent || logging {
         None
     } else {
         let spinner = indicatif::ProgressBar::new_spinner();
@@ -51,22 +145,137 @@ async fn main() -> Result<(), Box<dyn Error>> {
         Some(spinner)
     };
 
-    let did_sync = if sync::should(&config) {
+    sync::upgrade(&config, &mut conn)?;
+    let did_sync = if sync::should(&config, &conn) || mode == args::Mode::Sync {
         if let Some(spinner) = &spinner {
             spinner.set_message("syncing pkg-db…");
         }
+        if flags.stream {
+            stream::emit(json!(Event::Syncing));
+        }
         sync::replace(&config, &mut conn).await?;
         true
     } else {
         false
     };
 
+    match mode {
+        args::Mode::Sync => {
+            if let Some(spinner) = &spinner {
+                spinner.finish_and_clear();
+            }
+            return Ok(());
+        }
+        args::Mode::Query => {
+            if let Some(spinner) = &spinner {
+                spinner.finish_and_clear();
+            }
+            if !query::query(&args, &conn, &user_config, flags.json)? {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        args::Mode::Info | args::Mode::Tree => {
+            if let Some(spinner) = &spinner {
+                spinner.finish_and_clear();
+            }
+            let pkgspecs: Vec<String> = plus.iter().chain(&args).cloned().collect();
+            return if mode == args::Mode::Info {
+                query::info(&pkgspecs, &conn, &config, &user_config, flags.json).await
+            } else {
+                query::tree(&pkgspecs, &conn, &config, &user_config, flags.json)
+            };
+        }
+        _ => (),
+    }
+
     if let Some(spinner) = &spinner {
         spinner.set_message("resolving pkg graph…");
     }
+    if flags.stream {
+        stream::emit(json!(Event::Resolving));
+    }
 
     let mut pkgs = vec![];
 
+    if flags.dev {
+        let cwd = std::env::current_dir()?;
+        let mut manifest = manifest::find(&cwd)?.ok_or_else(|| {
+            format!(
+                "no pkgx manifest ({}) or version files in {} or its parents",
+                manifest::FILENAMES.join(", "),
+                cwd.display()
+            )
+        })?;
+        // eg. `node@20` in pkgx.yaml and `nodejs.org` from `.nvmrc`
+        manifest.dedup(|dep| {
+            let provider = which(&dep.project, &[], &conn, &user_config, |_, _| None);
+            provider.ok().map(|provider| provider.project)
+        });
+        plus.extend(manifest.deps.iter().map(|dep| dep.to_string()));
+    }
+
+    // scripts may be run by a pkgx provided interpreter, see below
+    let script = args
+        .first()
+        .filter(|arg| !find_program && arg.contains('/'))
+        .map(PathBuf::from)
+        .filter(|path| path.is_file());
+    let shebang = match &script {
+        Some(path) => script::shebang(path)?,
+        None => None,
+    };
+    let parsed_shebang = shebang
+        .as_deref()
+        .and_then(script::parse_shebang)
+        // interpreters that exist at absolute paths are left to the system
+        .filter(|shebang| {
+            !(shebang.program.starts_with('/') && Path::new(&shebang.program).is_file())
+        });
+    if let Some(shebang) = &parsed_shebang {
+        plus.extend(shebang.plus.iter().cloned());
+    }
+
+    // scripts may declare what they need, eg. `# pkgx: +jq python@3.12`
+    // including those that invoke us via their shebang, ie. `pkgx bash ./script`
+    let declaring_script = script.clone().or_else(|| {
+        let path = PathBuf::from(args.get(1).filter(|_| find_program)?);
+        let line = script::shebang(&path).ok()??;
+        line.split_whitespace()
+            .any(|word| word.rsplit('/').next() == Some("pkgx"))
+            .then_some(path)
+    });
+    if let Some(path) = &declaring_script {
+        plus.extend(script::deps(path)?);
+    }
+
+    // explicitly requested projects disambiguate, eg. `pkgx +yarnpkg.com yarn`
+    let requested: Vec<String> = plus
+        .iter()
+        .filter_map(|pkgspec| PackageReq::parse(pkgspec).ok())
+        .map(|pkgreq| pkgreq.project)
+        .collect();
+
+    let disambiguate = |cmd: &str, ranked: &[String]| {
+        let choose = || {
+            if prompt::is_interactive() {
+                prompt::disambiguate(cmd, ranked, &config)
+            } else {
+                let choice = ranked.first().cloned();
+                if let Some(project) = &choice {
+                    eprintln!("multiple projects provide `{}`, using: {}", cmd, project);
+                }
+                choice
+            }
+        };
+        match &spinner {
+            Some(spinner) => spinner.suspend(choose),
+            None => choose(),
+        }
+    };
+
+    let mut program = None;
+
     if find_program {
         let PackageReq {
             constraint,
@@ -75,31 +284,76 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
         args[0] = cmd.clone(); // invoke eg. `node` rather than eg. `node@20`
 
-        let project = match which(&cmd, &conn).await {
+        let provider = match which(&cmd, &requested, &conn, &user_config, disambiguate) {
             Err(WhichError::CmdNotFound(cmd)) => {
                 if !did_sync {
                     if let Some(spinner) = &spinner {
                         let msg = format!("{} not found, syncing…", cmd);
                         spinner.set_message(msg);
                     }
+                    if flags.stream {
+                        stream::emit(json!(Event::Syncing));
+                    }
                     // cmd not found ∴ sync in case it is new
                     sync::replace(&config, &mut conn).await?;
                     if let Some(spinner) = &spinner {
                         spinner.set_message("resolving pkg graph…");
                     }
-                    which(&cmd, &conn).await
+                    which(&cmd, &requested, &conn, &user_config, disambiguate)
                 } else {
                     Err(WhichError::CmdNotFound(cmd))
                 }
             }
             Err(err) => Err(err),
-            Ok(project) => Ok(project),
-        }?;
+            Ok(provider) => Ok(provider),
+        }
+        .map_err(libpkgx::Error::from)?;
 
         pkgs.push(PackageReq {
-            project,
-            constraint,
+            project: provider.project.clone(),
+            constraint: constraint.intersect(&provider.constraint)?,
         });
+        program = Some(provider);
+    }
+
+    let mut interpreter = None;
+
+    if let Some(shebang) = parsed_shebang {
+        // eg. `python3` for a `/usr/bin/python3` the system lacks
+        let program = shebang.program.rsplit('/').next().unwrap();
+        let PackageReq {
+            project: cmd,
+            constraint,
+        } = PackageReq::parse(program)?;
+        match which(&cmd, &requested, &conn, &user_config, disambiguate) {
+            Ok(provider) => {
+                pkgs.push(PackageReq {
+                    project: provider.project.clone(),
+                    constraint: constraint.intersect(&provider.constraint)?,
+                });
+                let mut args = vec![cmd];
+                args.extend(shebang.args);
+                interpreter = Some(Interpreter {
+                    project: provider.project,
+                    args,
+                });
+            }
+            // the system will have to make do
+            Err(WhichError::CmdNotFound(_)) => {}
+            Err(err) => return Err(libpkgx::Error::from(err).into()),
+        }
+    } else if let (Some(path), None) = (&script, &shebang) {
+        // scripts without a shebang are run by whatever interprets their extension
+        if let Some(extension) = script::extension(path) {
+            match which::interpreter(extension, &requested, &conn, &user_config, disambiguate) {
+                Ok(found) => {
+                    pkgs.push(PackageReq::parse(&found.project)?);
+                    interpreter = Some(found);
+                }
+                Err(WhichError::CmdNotFound(_)) => {}
+                Err(err) => return Err(libpkgx::Error::from(err).into()),
+            }
+        }
     }
 
     for pkgspec in plus {
@@ -118,10 +372,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 constraint,
             });
         } else {
-            let project = which(&project_or_cmd, &conn).await?;
+            let provider = which(
+                &project_or_cmd,
+                &requested,
+                &conn,
+                &user_config,
+                disambiguate,
+            )
+            .map_err(libpkgx::Error::from)?;
             pkgs.push(PackageReq {
-                project,
-                constraint,
+                project: provider.project,
+                constraint: constraint.intersect(&provider.constraint)?,
             });
         }
     }
@@ -142,6 +403,12 @@ async fn main() -> Result<(), Box<dyn Error>> {
     .await?;
 
     let resolution = resolve(graph, &config).await?;
+    if flags.stream {
+        for installation in &resolution.installed {
+            let pkg = installation.pkg.clone();
+            stream::emit(json!(Event::Cached { pkg }));
+        }
+    }
 
     let spinner_clone = spinner.clone();
     let clear_progress_bar = move || {
@@ -152,15 +419,31 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let mut installations = resolution.installed;
     if !resolution.pending.is_empty() {
-        let pb = spinner.map(|spinner| {
-            configure_bar(&spinner);
-            Arc::new(MultiProgressBar { pb: spinner })
-        });
-        let installed = install_multi::install_multi(&resolution.pending, &config, pb).await?;
-        installations.extend(installed);
+        // a bar per download replaces the spinner
+        let (events, renderer) = if flags.stream {
+            let (tx, rx) = events::channel();
+            (Some(tx), Some(tokio::spawn(stream::render(rx))))
+        } else if let Some(spinner) = &spinner {
+            spinner.finish_and_clear();
+            let (tx, rx) = events::channel();
+            (Some(tx), Some(tokio::spawn(progress::render(rx))))
+        } else {
+            (None, None)
+        };
+        let installed = install_multi::install_multi(&resolution.pending, &config, events).await;
+        // the sender is gone now so this finishes
+        if let Some(renderer) = renderer {
+            renderer.await?;
+        }
+        installations.extend(installed?);
     }
 
-    let env = env::map(&installations);
+    let rules = env::rules(&installations, &conn, &user_config)?;
+    let env = env::map(&installations, &rules);
+    let values = env::values(&installations, &rules);
+    if flags.stream {
+        stream::emit(json!({ "event": "env", "pkgs": installations, "env": env }));
+    }
 
     if !args.is_empty() {
         let pkgx_lvl = std::env::var("PKGX_LVL")
@@ -172,7 +455,20 @@ async fn main() -> Result<(), Box<dyn Error>> {
             return Err("PKGX_LVL exceeded: https://github.com/orgs/pkgxdev/discussions/11".into());
         }
 
-        let cmd = if find_program {
+        let cmd = if let Some(interpreter) = interpreter {
+            let mut interpreter_args = interpreter.args;
+            let program = interpreter_args.remove(0);
+            let paths = env.get("PATH").cloned().unwrap_or_default();
+            // eg. `deno run ./script.ts`
+            args.splice(0..0, interpreter_args);
+            utils::find_program(&program, &paths).await?
+        } else if let Some(path) = program
+            .as_ref()
+            .and_then(|provider| utils::provided_program(provider, &installations))
+        {
+            args.remove(0);
+            path
+        } else if find_program {
             utils::find_program(&args.remove(0), &env["PATH"]).await?
         } else if args[0].contains('/') {
             // user specified a path to program which we should use
@@ -185,7 +481,8 @@ async fn main() -> Result<(), Box<dyn Error>> {
             if let Some(pkgpaths) = env.get("PATH") {
                 paths.append(&mut pkgpaths.clone());
             }
-            if let Ok(syspaths) = std::env::var("PATH") {
+            // unless `--pure` where the system is off limits
+            if let Some(syspaths) = std::env::var("PATH").ok().filter(|_| !flags.pure) {
                 paths.extend(
                     syspaths
                         .split(':')
@@ -195,24 +492,61 @@ async fn main() -> Result<(), Box<dyn Error>> {
             }
             utils::find_program(&args.remove(0), &paths).await?
         };
-        let env = env::mix(env);
-        let mut env = env::mix_runtime(&env, &installations, &conn)?;
+        let mut env = if flags.pure {
+            env::pure(env)
+        } else {
+            env::mix(env)
+        };
+        env.extend(values);
+        let base = env::inherited(flags.pure);
+        let mut env = env::mix_runtime(&env, &base, &installations, &conn)?;
 
         // fork bomb protection
         env.insert("PKGX_LVL".to_string(), pkgx_lvl.to_string());
 
         clear_progress_bar();
+        tracing::debug!(cmd, ?args, "executing");
 
+        if flags.sandbox {
+            let status = sandboxed(&cmd, &args, &env, &installations, &config, &flags)?;
+            std::process::exit(status);
+        }
+        if flags.spawn {
+            let output = run::run(&cmd, &args, &env, false)?;
+            std::process::exit(output.status);
+        }
         execve(cmd, args, env)
-    } else if !env.is_empty() {
+    } else if flags.explain {
         clear_progress_bar();
 
-        if !flags.json {
-            let env = env.iter().map(|(k, v)| (k.clone(), v.join(":"))).collect();
-            let env = env::mix_runtime(&env, &installations, &conn)?;
-            for (key, value) in env {
-                println!("{}=\"{}${{{}:+:${}}}\"", key, value, key, key);
-            }
+        let contributions = env::explain(&installations, &rules, &conn)?;
+        let current = std::env::vars().collect();
+        if flags.json {
+            println!("{}", explain::json(&contributions, &current));
+        } else {
+            print!("{}", explain::table(&contributions, &current));
+        }
+        Ok(())
+    } else if !env.is_empty() || !values.is_empty() || hook_key.is_some() {
+        clear_progress_bar();
+
+        let mut joined: HashMap<String, String> =
+            env.iter().map(|(k, v)| (k.clone(), v.join(":"))).collect();
+        joined.extend(values);
+        let base = env::inherited(false);
+        let runtime = env::mix_runtime(&joined, &base, &installations, &conn)?;
+        let changes = shell::changes(&env, runtime);
+        let shell = flags.shell.unwrap_or_else(Shell::detect);
+        let current = std::env::vars().collect();
+
+        if let Some(key) = hook_key {
+            env_cache::put(&key, &installations, &changes, &config)?;
+            print!("{}", hook_code(shell, &current, &changes, &key));
+        } else if !flags.json {
+            print!(
+                "{}",
+                shell::render(shell, &shell::activate(&current, &changes))
+            );
         } else {
             let json = json!({
                 "pkgs": installations,
@@ -228,109 +562,66 @@ async fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
-#[derive(Debug)]
-pub enum WhichError {
-    CmdNotFound(String),
-    MultipleProjects(String, Vec<String>),
-    DbError(rusqlite::Error),
-}
-
-impl std::fmt::Display for WhichError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            WhichError::CmdNotFound(cmd) => write!(f, "cmd not found: {}", cmd),
-            WhichError::MultipleProjects(cmd, projects) => {
-                write!(f, "multiple projects found for {}: {:?}", cmd, projects)
-            }
-            WhichError::DbError(err) => write!(f, "db error: {}", err),
-        }
+/// runs `cmd` seeing only its `installations`, the current directory and
+/// anything the user `--allow`s
+#[cfg(target_os = "linux")]
+fn sandboxed(
+    cmd: &str,
+    args: &[String],
+    env: &HashMap<String, String>,
+    installations: &[Installation],
+    config: &Config,
+    flags: &args::Flags,
+) -> Result<i32, Box<dyn Error>> {
+    let mut sandbox = libpkgx::sandbox::Sandbox::new(installations, config)?;
+    sandbox.network = !flags.offline;
+    for path in &flags.allow {
+        sandbox.read_write.push(std::path::absolute(path)?);
     }
+    let command = sandbox.command(cmd, args, env)?;
+    Ok(run::run_command(command, false)?.status)
 }
 
-impl std::error::Error for WhichError {}
-
-async fn which(cmd: &String, conn: &Connection) -> Result<String, WhichError> {
-    let candidates = pantry_db::which(cmd, conn).map_err(WhichError::DbError)?;
-    if candidates.len() == 1 {
-        Ok(candidates[0].clone())
-    } else if candidates.is_empty() {
-        return Err(WhichError::CmdNotFound(cmd.clone()));
-    } else {
-        return Err(WhichError::MultipleProjects(cmd.clone(), candidates));
-    }
-}
-
-struct MultiProgressBar {
-    pb: ProgressBar,
+#[cfg(not(target_os = "linux"))]
+fn sandboxed(
+    _cmd: &str,
+    _args: &[String],
+    _env: &HashMap<String, String>,
+    _installations: &[Installation],
+    _config: &Config,
+    _flags: &args::Flags,
+) -> Result<i32, Box<dyn Error>> {
+    Err("--sandbox is only supported on Linux".into())
 }
 
-impl libpkgx::install_multi::ProgressBarExt for MultiProgressBar {
-    fn inc(&self, n: u64) {
-        self.pb.inc(n);
-    }
-
-    fn inc_length(&self, n: u64) {
-        self.pb.inc_length(n);
-    }
-}
-
-// ProgressBar is Send + Sync
-unsafe impl Send for MultiProgressBar {}
-unsafe impl Sync for MultiProgressBar {}
-
-fn configure_bar(pb: &ProgressBar) {
-    pb.set_length(1);
-    pb.set_style(
-        ProgressStyle::with_template(
-            "{elapsed:.dim} ❲{wide_bar:.red}❳ {percent}% {bytes_per_sec:.dim} {bytes:.dim}",
-        )
-        .unwrap()
-        .with_key("elapsed", |state: &ProgressState, w: &mut dyn Write| {
-            let s = state.elapsed().as_secs_f64();
-            let precision = precision(s);
-            write!(w, "{:.precision$}s", s, precision = precision).unwrap()
-        })
-        .with_key("bytes", |state: &ProgressState, w: &mut dyn Write| {
-            let (right, divisor) = pretty_size(state.len().unwrap());
-            let left = state.pos() as f64 / divisor as f64;
-            let leftprecision = precision(left);
-            write!(
-                w,
-                "{:.precision$}/{}",
-                left,
-                right,
-                precision = leftprecision
-            )
-            .unwrap()
-        })
-        .progress_chars("⚯ "),
-    );
-    pb.enable_steady_tick(Duration::from_millis(50));
-}
-
-fn pretty_size(n: u64) -> (String, u64) {
-    let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
-
-    // number of 1024s
-    let thousands = n.max(1).ilog(1024).clamp(0, units.len() as u32 - 1) as usize;
-    // size in the appropriate unit
-    let size = n as f64 / 1024.0f64.powi(thousands as i32);
-    // the divisor to get back to bytes
-    let divisor = 1024u64.pow(thousands as u32);
-    // number of decimal places to show (0 if we're bytes. no fractional bytes. come on.)
-    let precision = if thousands == 0 { 0 } else { precision(size) };
-
-    let formatted = format!(
-        "{:.precision$} {}",
-        size,
-        units[thousands],
-        precision = precision
-    );
-
-    (formatted, divisor)
+/// `libpkgx::Error::code()` and the exit status for it, or `error` for our own errors
+fn error_code(err: &(dyn Error + 'static)) -> (&'static str, i32) {
+    let code = match err.downcast_ref::<libpkgx::Error>() {
+        Some(err) => err.code(),
+        None if err.is::<std::io::Error>() => "io",
+        None => "error",
+    };
+    let status = match code {
+        "invalid" => 2,
+        "not-found" => 3,
+        "conflict" => 4,
+        "network" => 5,
+        "checksum" => 6,
+        "lock" => 7,
+        "pantry" => 8,
+        _ => 1,
+    };
+    (code, status)
 }
 
-fn precision(n: f64) -> usize {
-    // 1 > 1.00, 10 > 10.0, 100 > 100
-    2 - (n.log10().clamp(0.0, 2.0) as usize)
+/// activates `changes` for the directory hook, emitting only what differs
+fn hook_code(
+    shell: Shell,
+    current: &HashMap<String, String>,
+    changes: &[(String, Change)],
+    key: &str,
+) -> String {
+    let mut assignments = shell::activate(current, changes);
+    assignments.push((shell::DEV.to_string(), Some(key.to_string())));
+    shell::render(shell, &shell::diff(current, assignments))
 }
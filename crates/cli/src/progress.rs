@@ -0,0 +1,108 @@
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+use libpkgx::events::{Event, Receiver};
+
+/// Draws a bar per download until every sender for `events` is dropped.
+pub async fn render(mut events: Receiver) {
+    let multi = MultiProgress::new();
+    let mut bars: HashMap<String, ProgressBar> = HashMap::new();
+    while let Some(event) = events.recv().await {
+        match event {
+            Event::DownloadStarted { pkg, size } => {
+                // again if the download is retried
+                if let Some(bar) = bars.get(&pkg.project) {
+                    bar.reset();
+                    bar.set_length(size.unwrap_or(0));
+                    continue;
+                }
+                // an unknown size fills as the chunks arrive
+                let bar = multi.add(ProgressBar::new(size.unwrap_or(0)));
+                configure_bar(&bar);
+                bar.set_prefix(pkg.to_string());
+                bars.insert(pkg.project, bar);
+            }
+            Event::Chunk { pkg, n } => {
+                if let Some(bar) = bars.get(&pkg.project) {
+                    if bar.length() <= Some(bar.position() + n) {
+                        bar.set_length(bar.position() + n);
+                    }
+                    bar.inc(n);
+                }
+            }
+            Event::Extracting { pkg } => {
+                if let Some(bar) = bars.get(&pkg.project) {
+                    bar.set_message("extracting…");
+                }
+            }
+            Event::Installed { pkg } => {
+                if let Some(bar) = bars.get(&pkg.project) {
+                    bar.finish_with_message("installed");
+                }
+            }
+            Event::Failed { pkg, .. } => {
+                if let Some(bar) = bars.get(&pkg.project) {
+                    bar.abandon_with_message("failed");
+                }
+            }
+            _ => {}
+        }
+    }
+    let _ = multi.clear();
+}
+
+fn configure_bar(pb: &ProgressBar) {
+    pb.set_style(
+        ProgressStyle::with_template(
+            "{prefix} {elapsed:.dim} ❲{wide_bar:.red}❳ {percent}% {bytes_per_sec:.dim} {bytes:.dim} {msg:.dim}",
+        )
+        .unwrap()
+        .with_key("elapsed", |state: &ProgressState, w: &mut dyn Write| {
+            let s = state.elapsed().as_secs_f64();
+            let precision = precision(s);
+            write!(w, "{:.precision$}s", s, precision = precision).unwrap()
+        })
+        .with_key("bytes", |state: &ProgressState, w: &mut dyn Write| {
+            let (right, divisor) = pretty_size(state.len().unwrap());
+            let left = state.pos() as f64 / divisor as f64;
+            let leftprecision = precision(left);
+            write!(
+                w,
+                "{:.precision$}/{}",
+                left,
+                right,
+                precision = leftprecision
+            )
+            .unwrap()
+        })
+        .progress_chars("⚯ "),
+    );
+    pb.enable_steady_tick(Duration::from_millis(50));
+}
+
+pub(crate) fn pretty_size(n: u64) -> (String, u64) {
+    let units = ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB", "ZiB", "YiB"];
+
+    // number of 1024s
+    let thousands = n.max(1).ilog(1024).clamp(0, units.len() as u32 - 1) as usize;
+    // size in the appropriate unit
+    let size = n as f64 / 1024.0f64.powi(thousands as i32);
+    // the divisor to get back to bytes
+    let divisor = 1024u64.pow(thousands as u32);
+    // number of decimal places to show (0 if we're bytes. no fractional bytes. come on.)
+    let precision = if thousands == 0 { 0 } else { precision(size) };
+
+    let formatted = format!(
+        "{:.precision$} {}",
+        size,
+        units[thousands],
+        precision = precision
+    );
+
+    (formatted, divisor)
+}
+
+pub(crate) fn precision(n: f64) -> usize {
+    // 1 > 1.00, 10 > 10.0, 100 > 100
+    2 - (n.log10().clamp(0.0, 2.0) as usize)
+}
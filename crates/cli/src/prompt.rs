@@ -0,0 +1,48 @@
+use std::io::{BufRead, IsTerminal, Write};
+
+use libpkgx::{config::Config, user_config::UserConfig};
+
+pub fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Asks the user which of the `ranked` projects should provide `cmd`.
+/// Optionally remembers the choice in the user’s `config.yml`.
+pub fn disambiguate(cmd: &str, ranked: &[String], config: &Config) -> Option<String> {
+    let mut stderr = std::io::stderr();
+    let mut stdin = std::io::stdin().lock();
+
+    eprintln!("multiple projects provide `{}`:", cmd);
+    for (i, project) in ranked.iter().enumerate() {
+        eprintln!("  {}. {}", i + 1, project);
+    }
+    eprint!("choose [1]: ");
+    stderr.flush().ok()?;
+
+    let mut line = String::new();
+    stdin.read_line(&mut line).ok()?;
+    let line = line.trim();
+    let choice = if line.is_empty() {
+        ranked.first()?.clone()
+    } else {
+        let n: usize = line.parse().ok()?;
+        ranked.get(n.checked_sub(1)?)?.clone()
+    };
+
+    eprint!("always use {} for `{}`? [y/N]: ", choice, cmd);
+    stderr.flush().ok()?;
+
+    let mut line = String::new();
+    stdin.read_line(&mut line).ok()?;
+    if matches!(line.trim(), "y" | "Y" | "yes") {
+        let saved = UserConfig::load(config).and_then(|mut user_config| {
+            user_config.programs.insert(cmd.to_string(), choice.clone());
+            user_config.save(config)
+        });
+        if let Err(err) = saved {
+            eprintln!("warning: couldn’t save preference: {}", err);
+        }
+    }
+
+    Some(choice)
+}
@@ -0,0 +1,220 @@
+use std::error::Error;
+
+use libpkgx::{
+    cellar,
+    config::Config,
+    pantry_db,
+    types::PackageReq,
+    user_config::UserConfig,
+    which::{which, WhichError},
+};
+use rusqlite::Connection;
+use serde_json::json;
+
+/// `--query`: prints the pkg providing each program, failing if any are unknown
+pub fn query(
+    programs: &[String],
+    conn: &Connection,
+    user_config: &UserConfig,
+    json: bool,
+) -> Result<bool, Box<dyn Error>> {
+    let mut found = true;
+    let mut rv = vec![];
+    for program in programs {
+        match which(program, &[], conn, user_config, |_, _| None) {
+            Ok(provider) => rv.push(json!({
+                "program": program,
+                "project": provider.project,
+                "path": provider.path,
+            })),
+            Err(WhichError::CmdNotFound(_)) => {
+                found = false;
+                if !json {
+                    eprintln!("pkgx: nothing provides: {}", program);
+                }
+            }
+            Err(WhichError::MultipleProjects(_, projects)) => {
+                for project in projects {
+                    rv.push(json!({ "program": program, "project": project }));
+                }
+            }
+            Err(err) => return Err(libpkgx::Error::from(err).into()),
+        }
+    }
+
+    if json {
+        println!("{}", json!(rv));
+    } else {
+        for entry in rv {
+            println!("{}", entry["project"].as_str().unwrap_or_default());
+        }
+    }
+    Ok(found)
+}
+
+/// `--info`: what the pantry knows about each pkg and which versions are installed
+pub async fn info(
+    pkgspecs: &[String],
+    conn: &Connection,
+    config: &Config,
+    user_config: &UserConfig,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut rv = vec![];
+    for pkgspec in pkgspecs {
+        let rq = pkgreq(pkgspec, conn, config, user_config)?;
+        let project = &rq.project;
+
+        let programs = pantry_db::programs_for_project(project, conn)?;
+        let deps = pantry_db::deps_for_project(project, conn)?;
+        let companions = pantry_db::companions_for_projects(std::slice::from_ref(project), conn)?;
+        let mut runtime_env: Vec<(String, String)> =
+            pantry_db::runtime_env_for_project(project, conn)?
+                .into_iter()
+                .collect();
+        runtime_env.sort();
+        let installed: Vec<String> = cellar::ls(project, config)
+            .await
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|i| rq.constraint.satisfies(&i.pkg.version))
+            .map(|i| i.pkg.version.to_string())
+            .collect();
+
+        rv.push(json!({
+            "project": project,
+            "programs": programs,
+            "dependencies": deps.iter().map(|dep| dep.to_string()).collect::<Vec<_>>(),
+            "companions": companions.iter().map(|dep| dep.to_string()).collect::<Vec<_>>(),
+            "runtime-env": runtime_env.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>(),
+            "installed": installed,
+        }));
+    }
+
+    if json {
+        println!("{}", json!(rv));
+        return Ok(());
+    }
+    for entry in rv {
+        println!("{}", entry["project"].as_str().unwrap_or_default());
+        for key in [
+            "programs",
+            "dependencies",
+            "companions",
+            "runtime-env",
+            "installed",
+        ] {
+            let values: Vec<&str> = entry[key]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|value| value.as_str())
+                .collect();
+            if !values.is_empty() {
+                println!("  {}: {}", key, values.join(" "));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `--tree`: the dependency graph of each pkg as the pantry declares it
+pub fn tree(
+    pkgspecs: &[String],
+    conn: &Connection,
+    config: &Config,
+    user_config: &UserConfig,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut rv = vec![];
+    for pkgspec in pkgspecs {
+        let rq = pkgreq(pkgspec, conn, config, user_config)?;
+        rv.push(node(&rq, conn, &mut vec![])?);
+    }
+
+    if json {
+        println!("{}", json!(rv));
+    } else {
+        for node in &rv {
+            print!("{}", render(node, ""));
+        }
+    }
+    Ok(())
+}
+
+// `stack` is the path from the root, so cycles don’t recurse forever
+fn node(
+    rq: &PackageReq,
+    conn: &Connection,
+    stack: &mut Vec<String>,
+) -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut deps = vec![];
+    if !stack.contains(&rq.project) {
+        stack.push(rq.project.clone());
+        for dep in pantry_db::deps_for_project(&rq.project, conn)? {
+            deps.push(node(&dep, conn, stack)?);
+        }
+        stack.pop();
+    }
+    Ok(json!({ "pkg": rq.to_string(), "dependencies": deps }))
+}
+
+fn render(node: &serde_json::Value, indent: &str) -> String {
+    let mut rv = format!("{}\n", node["pkg"].as_str().unwrap_or_default());
+    let deps = node["dependencies"].as_array().cloned().unwrap_or_default();
+    for (i, dep) in deps.iter().enumerate() {
+        let last = i == deps.len() - 1;
+        let (branch, continuation) = if last {
+            ("└─ ", "   ")
+        } else {
+            ("├─ ", "│  ")
+        };
+        let child_indent = format!("{}{}", indent, continuation);
+        rv.push_str(&format!(
+            "{}{}{}",
+            indent,
+            branch,
+            render(dep, &child_indent)
+        ));
+    }
+    rv
+}
+
+/// `--list`: every installed pkg
+pub async fn list(config: &Config, json: bool) -> Result<(), Box<dyn Error>> {
+    let installations = cellar::installed(config).await?;
+    if json {
+        println!("{}", json!(installations));
+    } else {
+        for installation in installations {
+            println!("{}", installation.pkg);
+        }
+    }
+    Ok(())
+}
+
+// pkgspecs may name a program rather than a project, eg. `node@20`
+pub(crate) fn pkgreq(
+    pkgspec: &str,
+    conn: &Connection,
+    config: &Config,
+    user_config: &UserConfig,
+) -> Result<PackageReq, Box<dyn Error>> {
+    let rq = PackageReq::parse(pkgspec)?;
+    let projects = config.pantry_dir.join("projects");
+    if projects.join(&rq.project).join("package.yml").is_file() {
+        return Ok(rq);
+    }
+    let provider =
+        which(&rq.project, &[], conn, user_config, |_, _| None).map_err(libpkgx::Error::from)?;
+    let constraint = rq.constraint.intersect(&provider.constraint).map_err(|_| {
+        libpkgx::Error::Conflict(format!(
+            "{} isn’t provided by {}",
+            pkgspec, provider.project
+        ))
+    })?;
+    Ok(PackageReq {
+        project: provider.project,
+        constraint,
+    })
+}
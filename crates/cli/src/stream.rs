@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use libpkgx::{
+    events::{self, Event, Receiver},
+    types::Package,
+};
+use serde_json::{json, Value};
+
+/// `--json=stream`: an object per line with what happened as `event` and the
+/// `events::SCHEMA` version as `v`.
+pub fn line(mut event: Value) -> String {
+    event["v"] = json!(events::SCHEMA);
+    event.to_string()
+}
+
+/// stdout is the program’s so the stream is on stderr
+pub fn emit(event: Value) {
+    eprintln!("{}", line(event));
+}
+
+/// Emits `events` until every sender for them is dropped.
+pub async fn render(mut events: Receiver) {
+    let mut throttle = Throttle::default();
+    while let Some(event) = events.recv().await {
+        for event in throttle.push(event, Instant::now()) {
+            emit(json!(event));
+        }
+    }
+    for event in throttle.flush() {
+        emit(json!(event));
+    }
+}
+
+/// the most often a pkg’s chunks are emitted, unless another 1% arrived
+const CHUNK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Merges each pkg’s `Chunk`s so there’s one per 1% of the download or per
+/// `CHUNK_INTERVAL`, whichever comes first. Whatever is left over is emitted
+/// before the pkg’s next other event.
+#[derive(Default)]
+pub struct Throttle {
+    // by pkg
+    downloads: HashMap<String, Download>,
+}
+
+struct Download {
+    pkg: Package,
+    size: Option<u64>,
+    pending: u64,
+    emitted: Instant,
+}
+
+impl Throttle {
+    /// The events to emit now that `event` happened.
+    pub fn push(&mut self, event: Event, now: Instant) -> Vec<Event> {
+        match event {
+            Event::DownloadStarted { ref pkg, size } => {
+                let mut rv: Vec<Event> = self.take(&pkg.to_string()).into_iter().collect();
+                self.downloads.insert(
+                    pkg.to_string(),
+                    Download {
+                        pkg: pkg.clone(),
+                        size,
+                        pending: 0,
+                        emitted: now,
+                    },
+                );
+                rv.push(event);
+                rv
+            }
+            Event::Chunk { pkg, n } => {
+                let Some(download) = self.downloads.get_mut(&pkg.to_string()) else {
+                    return vec![Event::Chunk { pkg, n }];
+                };
+                download.pending += n;
+                let percent = download
+                    .size
+                    .is_some_and(|size| download.pending * 100 >= size);
+                if percent || now.duration_since(download.emitted) >= CHUNK_INTERVAL {
+                    let n = std::mem::take(&mut download.pending);
+                    download.emitted = now;
+                    vec![Event::Chunk { pkg, n }]
+                } else {
+                    vec![]
+                }
+            }
+            Event::Cached { ref pkg }
+            | Event::Extracting { ref pkg }
+            | Event::Installed { ref pkg }
+            | Event::Failed { ref pkg, .. } => {
+                let mut rv: Vec<Event> = self.take(&pkg.to_string()).into_iter().collect();
+                rv.push(event);
+                rv
+            }
+            event => vec![event],
+        }
+    }
+
+    /// The chunks not yet emitted, eg. once the events are over.
+    pub fn flush(&mut self) -> Vec<Event> {
+        let pkgs: Vec<String> = self.downloads.keys().cloned().collect();
+        pkgs.iter().filter_map(|pkg| self.take(pkg)).collect()
+    }
+
+    // the pkg’s pending bytes as a chunk, if any, its download is done with
+    fn take(&mut self, pkg: &str) -> Option<Event> {
+        let Download { pkg, pending, .. } = self.downloads.remove(pkg)?;
+        (pending > 0).then_some(Event::Chunk { pkg, n: pending })
+    }
+}
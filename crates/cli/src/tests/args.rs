@@ -0,0 +1,68 @@
+use crate::args::{parse_from, Args, ArgsError, Mode};
+
+fn parse(args: &[&str]) -> Result<Args, ArgsError> {
+    parse_from(args.iter().map(|arg| arg.to_string()))
+}
+
+fn error(args: &[&str]) -> String {
+    parse(args).unwrap_err().0
+}
+
+#[test]
+fn test_parse() {
+    let args = parse(&["+node@20", "-sj", "--dev", "npm", "--silent", "test"]).unwrap();
+    assert_eq!(args.mode, Mode::X);
+    assert_eq!(args.plus, vec!["node@20"]);
+    assert_eq!(args.args, vec!["npm", "--silent", "test"]);
+    assert!(args.find_program);
+    assert!(args.flags.silent && args.flags.json && args.flags.dev);
+
+    assert_eq!(parse(&["-vv", "node"]).unwrap().flags.verbose, 2);
+
+    let args = parse(&["--json=stream", "+node"]).unwrap();
+    assert!(args.flags.json && args.flags.stream);
+
+    let args = parse(&["+wget", "--", "git", "clone"]).unwrap();
+    assert_eq!(args.args, vec!["git", "clone"]);
+    assert!(!args.find_program);
+
+    let args = parse(&["--sandbox=offline", "--allow=/a", "--allow=/b", "./foo"]).unwrap();
+    assert!(args.flags.sandbox && args.flags.offline);
+    assert_eq!(args.flags.allow.len(), 2);
+    assert!(!args.find_program);
+
+    let args = parse(&["--shell=fish", "--shellcode"]).unwrap();
+    assert_eq!(args.mode, Mode::Shellcode);
+    assert!(args.flags.shell.is_some());
+
+    // modes take their operands even after other flags
+    let args = parse(&["-Q", "node", "--json", "python3.11"]).unwrap();
+    assert_eq!(args.mode, Mode::Query);
+    assert_eq!(args.args, vec!["node", "python3.11"]);
+    assert!(args.flags.json);
+
+    let args = parse(&["--tree", "+openssl.org"]).unwrap();
+    assert_eq!(args.mode, Mode::Tree);
+    assert_eq!(args.plus, vec!["openssl.org"]);
+
+    assert_eq!(parse(&["--query", "--help"]).unwrap().mode, Mode::Help);
+    assert_eq!(parse(&["-h", "--sync"]).unwrap().mode, Mode::Help);
+}
+
+#[test]
+fn test_parse_errors() {
+    assert_eq!(error(&["--bogus"]), "unknown option: --bogus");
+    assert_eq!(error(&["-sx"]), "unknown option: -x");
+    assert_eq!(error(&["--shell"]), "--shell needs a value: --shell=<sh>");
+    assert_eq!(error(&["--shell=csh"]), "unsupported shell: csh");
+    assert_eq!(error(&["--dev=1"]), "--dev doesn’t take a value");
+    assert_eq!(error(&["--sandbox=foo"]), "unknown --sandbox mode: foo");
+    assert_eq!(error(&["--json=yaml"]), "unknown --json mode: yaml");
+    assert_eq!(
+        error(&["--sync", "-Q"]),
+        "--sync and -Q can’t be used together"
+    );
+    assert_eq!(error(&["--query"]), "--query needs programs to look up");
+    assert_eq!(error(&["--info"]), "--info needs pkgs");
+    assert_eq!(error(&["--list", "node"]), "--list takes no arguments");
+}
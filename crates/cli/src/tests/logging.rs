@@ -0,0 +1,21 @@
+use crate::logging::directives;
+
+#[test]
+fn test_directives() {
+    let everything = Some("pkgx=trace,libpkgx=trace".to_string());
+    assert_eq!(directives(None, 0), None);
+    assert_eq!(
+        directives(None, 1),
+        Some("pkgx=debug,libpkgx=debug".to_string())
+    );
+    assert_eq!(directives(None, 2), everything);
+    assert_eq!(directives(Some("1"), 0), everything);
+    assert_eq!(
+        directives(Some("libpkgx::install=trace"), 0),
+        Some("libpkgx::install=trace".to_string())
+    );
+    // set but empty, eg. `PKGX_DEBUG= pkgx …`, is unset
+    assert_eq!(directives(Some(""), 0), None);
+    assert_eq!(directives(Some(""), 2), everything);
+    assert_eq!(directives(Some("0"), 0), None);
+}
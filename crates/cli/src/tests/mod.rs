@@ -1 +1,5 @@
-mod main;
+mod args;
+mod logging;
+mod progress;
+mod query;
+mod stream;
@@ -0,0 +1,42 @@
+use crate::query::pkgreq;
+use libpkgx::{config::Config, pantry_db, user_config::UserConfig};
+use libsemverator::range::Range as VersionReq;
+use rusqlite::Connection;
+
+#[test]
+fn test_pkgreq() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-query-{}", std::process::id()));
+    let config = Config {
+        pantry_dir: dir.join("pantry"),
+        dist_url: String::new(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    };
+    let entry = config.pantry_dir.join("projects/python.org");
+    std::fs::create_dir_all(&entry).unwrap();
+    let yaml = "provides: [bin/python, 'bin/python{{version.marketing}}']\n";
+    std::fs::write(entry.join("package.yml"), yaml).unwrap();
+    let mut conn = Connection::open_in_memory().unwrap();
+    pantry_db::cache(&config, &mut conn).unwrap();
+    let user_config = UserConfig::default();
+    let pkgreq = |pkgspec| pkgreq(pkgspec, &conn, &config, &user_config);
+
+    let rq = pkgreq("python3.11").unwrap();
+    assert_eq!(rq.project, "python.org");
+    assert_eq!(rq.constraint, VersionReq::parse("@3.11").unwrap());
+
+    let rq = pkgreq("python3.11@3.11.4").unwrap();
+    assert_eq!(rq.constraint, VersionReq::parse("@3.11.4").unwrap());
+
+    let rq = pkgreq("python@3").unwrap();
+    assert_eq!(rq.constraint, VersionReq::parse("@3").unwrap());
+
+    let err = pkgreq("python3.11@3.12").unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(
+        err.to_string(),
+        "python3.11@3.12 isn’t provided by python.org"
+    );
+}
@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+use libpkgx::{events::Event, types::Package};
+use libsemverator::semver::Semver as Version;
+use serde_json::json;
+
+use crate::stream::{line, Throttle};
+
+#[test]
+fn test_line() {
+    assert_eq!(line(json!(Event::Syncing)), r#"{"event":"syncing","v":1}"#);
+    assert_eq!(
+        line(json!({ "event": "env", "env": { "PATH": ["/a", "/b"] } })),
+        r#"{"env":{"PATH":["/a","/b"]},"event":"env","v":1}"#
+    );
+}
+
+#[test]
+fn test_throttle() {
+    let pkg = Package {
+        project: "foo.org".to_string(),
+        version: Version::parse("1.0.0").unwrap(),
+    };
+    let chunk = |n| Event::Chunk {
+        pkg: pkg.clone(),
+        n,
+    };
+    // the `n`s of the chunks, the other events by name
+    let summary = |events: Vec<Event>| -> Vec<String> {
+        events
+            .iter()
+            .map(|event| match event {
+                Event::Chunk { n, .. } => n.to_string(),
+                event => json!(event)["event"].as_str().unwrap().to_string(),
+            })
+            .collect()
+    };
+
+    let mut throttle = Throttle::default();
+    let start = Instant::now();
+    let started = Event::DownloadStarted {
+        pkg: pkg.clone(),
+        size: Some(1000),
+    };
+    assert_eq!(summary(throttle.push(started, start)), ["download-started"]);
+
+    // merged until there’s 1%
+    assert!(throttle.push(chunk(4), start).is_empty());
+    assert!(throttle.push(chunk(4), start).is_empty());
+    assert_eq!(summary(throttle.push(chunk(4), start)), ["12"]);
+
+    // or it has been a while
+    assert!(throttle.push(chunk(1), start).is_empty());
+    let later = start + Duration::from_millis(250);
+    assert_eq!(summary(throttle.push(chunk(1), later)), ["2"]);
+
+    // the rest comes before the pkg’s next event
+    assert!(throttle.push(chunk(3), later).is_empty());
+    let extracting = Event::Extracting { pkg: pkg.clone() };
+    assert_eq!(
+        summary(throttle.push(extracting, later)),
+        ["3", "extracting"]
+    );
+    assert!(throttle.flush().is_empty());
+
+    // or at the end
+    let started = Event::DownloadStarted {
+        pkg: pkg.clone(),
+        size: None,
+    };
+    throttle.push(started, start);
+    assert!(throttle.push(chunk(5), start).is_empty());
+    assert_eq!(summary(throttle.flush()), ["5"]);
+
+    // others aren’t held up
+    assert_eq!(
+        summary(throttle.push(Event::Resolving, start)),
+        ["resolving"]
+    );
+}
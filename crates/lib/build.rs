@@ -5,4 +5,25 @@ fn main() {
 
     println!("cargo:rustc-env=PKGX_DIST_URL={dist_url}");
     println!("cargo:rustc-env=PKGX_PANTRY_TARBALL_URL={pantry_url}");
+
+    // the C API’s header, from `ffi.rs` alone. The copy in `include/` is
+    // committed so consumers needn’t build us first, it is only updated when
+    // asked: `PKGX_UPDATE_HEADER=1 cargo build`
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("couldn’t read cbindgen.toml");
+    let header = cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{crate_dir}/src/ffi.rs"))
+        .generate()
+        .expect("couldn’t generate the C header");
+    let out_dir = std::env::var("OUT_DIR").unwrap();
+    header.write_to_file(format!("{out_dir}/pkgx.h"));
+    if std::env::var_os("PKGX_UPDATE_HEADER").is_some() {
+        header.write_to_file(format!("{crate_dir}/include/pkgx.h"));
+    }
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=PKGX_UPDATE_HEADER");
 }
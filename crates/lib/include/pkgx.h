@@ -0,0 +1,122 @@
+#ifndef PKGX_H
+#define PKGX_H
+
+/* generated from src/ffi.rs by build.rs (PKGX_UPDATE_HEADER=1 cargo build), don’t edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * `Error::code()` for C
+ */
+typedef enum PkgxStatus {
+  PKGX_STATUS_OK = 0,
+  PKGX_STATUS_NOT_FOUND = 1,
+  PKGX_STATUS_CONFLICT = 2,
+  PKGX_STATUS_NETWORK = 3,
+  PKGX_STATUS_CHECKSUM = 4,
+  PKGX_STATUS_LOCK = 5,
+  PKGX_STATUS_PANTRY = 6,
+  PKGX_STATUS_INVALID = 7,
+  PKGX_STATUS_IO = 8,
+  PKGX_STATUS_DB = 9,
+  /**
+   * a bug in libpkgx, the handle should not be used again
+   */
+  PKGX_STATUS_PANIC = 10,
+} PkgxStatus;
+
+/**
+ * Opaque, from `pkgx_new`. Not safe to use from several threads at once.
+ */
+typedef struct PkgxHandle PkgxHandle;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a handle configured like the `pkgx` command, ie. via `PKGX_DIR` etc.
+ * On failure `out` is set to null.
+ *
+ * # Safety
+ *
+ * `out` must be valid for writes. Free the handle with `pkgx_free`.
+ */
+enum PkgxStatus pkgx_new(struct PkgxHandle **out);
+
+/**
+ * # Safety
+ *
+ * `handle` must be from `pkgx_new` (or null) and not used afterwards.
+ */
+void pkgx_free(struct PkgxHandle *handle);
+
+/**
+ * Updates the pkg-db, the other functions do this if it is missing or outdated.
+ *
+ * # Safety
+ *
+ * `handle` must be from `pkgx_new`.
+ */
+enum PkgxStatus pkgx_sync(struct PkgxHandle *handle);
+
+/**
+ * The pkg that provides `cmd`: `{"project": "nodejs.org", "path": "bin/node"}`.
+ *
+ * # Safety
+ *
+ * `handle` must be from `pkgx_new`, `cmd` a C string and `out` valid for writes.
+ */
+enum PkgxStatus pkgx_which(struct PkgxHandle *handle, const char *cmd, char **out);
+
+/**
+ * Resolves whitespace separated `pkgspecs` (eg. `node@20 openssl.org`) and
+ * their deps: `{"pkgs": [{"project": …, "version": …}], "installed": […], "pending": […]}`.
+ *
+ * # Safety
+ *
+ * `handle` must be from `pkgx_new`, `pkgspecs` a C string and `out` valid for writes.
+ */
+enum PkgxStatus pkgx_resolve(struct PkgxHandle *handle, const char *pkgspecs, char **out);
+
+/**
+ * Like `pkgx_resolve` but installs what’s pending, outputs every installation:
+ * `[{"path": …, "project": …, "version": …}]`.
+ *
+ * # Safety
+ *
+ * `handle` must be from `pkgx_new`, `pkgspecs` a C string and `out` valid for writes.
+ */
+enum PkgxStatus pkgx_install(struct PkgxHandle *handle, const char *pkgspecs, char **out);
+
+/**
+ * Like `pkgx_install` but outputs the environment to run things in:
+ * `{"PATH": "…", …}`.
+ *
+ * # Safety
+ *
+ * `handle` must be from `pkgx_new`, `pkgspecs` a C string and `out` valid for writes.
+ */
+enum PkgxStatus pkgx_env(struct PkgxHandle *handle, const char *pkgspecs, char **out);
+
+/**
+ * Why the last call on this thread failed, valid until the next call.
+ */
+const char *pkgx_last_error(void);
+
+/**
+ * # Safety
+ *
+ * `string` must be from libpkgx (or null) and not used afterwards.
+ */
+void pkgx_string_free(char *string);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PKGX_H */
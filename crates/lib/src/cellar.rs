@@ -1,11 +1,11 @@
 use crate::config::Config;
 use crate::types::{Installation, Package, PackageReq};
+use crate::Error;
 use libsemverator::semver::Semver as Version;
-use std::error::Error;
 use std::path::PathBuf;
 use tokio::fs;
 
-pub async fn ls(project: &str, config: &Config) -> Result<Vec<Installation>, Box<dyn Error>> {
+pub async fn ls(project: &str, config: &Config) -> Result<Vec<Installation>, Error> {
     let d = config.pkgx_dir.join(project);
 
     if !fs::metadata(&d).await?.is_dir() {
@@ -39,7 +39,44 @@ pub async fn ls(project: &str, config: &Config) -> Result<Vec<Installation>, Box
     Ok(rv)
 }
 
-pub async fn resolve(pkgreq: &PackageReq, config: &Config) -> Result<Installation, Box<dyn Error>> {
+/// Everything installed in `config.pkgx_dir`, sorted.
+pub async fn installed(config: &Config) -> Result<Vec<Installation>, Error> {
+    let mut rv = vec![];
+    let mut stack = vec![config.pkgx_dir.clone()];
+
+    while let Some(dir) = stack.pop() {
+        let mut entries = match fs::read_dir(&dir).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let mut is_project = false;
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || !fs::symlink_metadata(entry.path()).await?.is_dir() {
+                continue;
+            }
+            // projects have `v*` versions, others are namespaces, eg. `github.com`
+            if name.starts_with('v') && Version::parse(&name[1..]).is_ok() {
+                is_project = true;
+            } else if name != "var" {
+                stack.push(entry.path());
+            }
+        }
+        if let Some(project) = dir
+            .strip_prefix(&config.pkgx_dir)
+            .ok()
+            .filter(|_| is_project)
+        {
+            rv.extend(ls(&project.to_string_lossy(), config).await?);
+        }
+    }
+
+    rv.sort_by(|a, b| (&a.pkg.project, &a.pkg.version).cmp(&(&b.pkg.project, &b.pkg.version)));
+    Ok(rv)
+}
+
+pub async fn resolve(pkgreq: &PackageReq, config: &Config) -> Result<Installation, Error> {
     let installations = ls(&pkgreq.project, config).await?;
 
     if let Some(i) = installations
@@ -50,7 +87,7 @@ pub async fn resolve(pkgreq: &PackageReq, config: &Config) -> Result<Installatio
         Ok(i.clone())
     } else {
         // If no matching version is found, return an error
-        Err(format!("couldn’t resolve {:?}", pkgreq).into())
+        Err(Error::NotFound(pkgreq.to_string()))
     }
 }
 
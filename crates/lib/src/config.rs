@@ -7,6 +7,11 @@ pub struct Config {
     pub pantry_dir: PathBuf,
     pub dist_url: String,
     pub pkgx_dir: PathBuf,
+    pub config_dir: PathBuf,
+    /// how many pkgs to download at once, `PKGX_JOBS`
+    pub jobs: usize,
+    /// how many times to retry failed downloads, `PKGX_RETRIES`
+    pub retries: u32,
 }
 
 impl Config {
@@ -14,10 +19,14 @@ impl Config {
         let pantry_dir = get_pantry_dir()?;
         let dist_url = get_dist_url();
         let pkgx_dir = get_pkgx_dir()?;
+        let config_dir = get_config_dir()?;
         Ok(Self {
             pantry_dir,
             dist_url,
             pkgx_dir,
+            config_dir,
+            jobs: get_number("PKGX_JOBS", 8),
+            retries: get_number("PKGX_RETRIES", 3),
         })
     }
 }
@@ -29,6 +38,13 @@ fn get_dist_url() -> String {
     env!("PKGX_DIST_URL").to_string()
 }
 
+fn get_number<T: std::str::FromStr>(key: &str, default: T) -> T {
+    env::var(key)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(default)
+}
+
 fn get_pantry_dir() -> io::Result<PathBuf> {
     if let Ok(env_dir) = env::var("PKGX_PANTRY_DIR") {
         let path = PathBuf::from(env_dir);
@@ -57,3 +73,15 @@ fn get_pkgx_dir() -> io::Result<PathBuf> {
     #[cfg(not(any(target_os = "macos", target_os = "linux")))]
     panic!("Unsupported platform")
 }
+
+fn get_config_dir() -> io::Result<PathBuf> {
+    if let Ok(env_dir) = env::var("PKGX_CONFIG_DIR") {
+        let path = PathBuf::from(env_dir);
+        if !path.is_absolute() {
+            return Ok(env::current_dir()?.join(path));
+        } else {
+            return Ok(path);
+        }
+    }
+    Ok(dirs_next::config_dir().unwrap().join("pkgx"))
+}
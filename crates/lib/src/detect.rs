@@ -0,0 +1,118 @@
+use crate::types::PackageReq;
+use crate::Error;
+use lazy_static::lazy_static;
+use libsemverator::range::Range as VersionReq;
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+lazy_static! {
+    static ref VERSION_REGEX: Regex = Regex::new(r"^v?(\d+(\.\d+)*)$").unwrap();
+    static ref CHANNEL_REGEX: Regex =
+        Regex::new(r#"(?m)^\s*channel\s*=\s*["']([^"']+)["']"#).unwrap();
+    static ref GO_REGEX: Regex = Regex::new(r"(?m)^go\s+(\d+(\.\d+)*)\s*$").unwrap();
+}
+
+/// Requirements implied by the version files of other ecosystems in `dir`,
+/// eg. `.nvmrc` or `go.mod`, along with the files they came from.
+pub fn detect(dir: &Path) -> Result<Vec<(PathBuf, PackageReq)>, Error> {
+    let mut rv = vec![];
+
+    let mut push = |filename: &str, project: &str, constraint: Option<VersionReq>| {
+        if let Some(constraint) = constraint {
+            let pkgreq = PackageReq {
+                project: project.to_string(),
+                constraint,
+            };
+            // eg. `.nvmrc` and `.node-version` both exist, the first wins
+            if !rv
+                .iter()
+                .any(|(_, x): &(_, PackageReq)| x.project == pkgreq.project)
+            {
+                rv.push((dir.join(filename), pkgreq));
+            }
+        }
+    };
+
+    for filename in [".node-version", ".nvmrc"] {
+        if let Some(content) = read(dir, filename)? {
+            push(filename, "nodejs.org", version_file(&content));
+        }
+    }
+    if let Some(content) = read(dir, "package.json")? {
+        match serde_json::from_str::<serde_json::Value>(&content) {
+            Ok(json) => {
+                let engine = json["engines"]["node"].as_str();
+                push("package.json", "nodejs.org", engine.and_then(range));
+            }
+            // it’s npm’s problem, not a reason to fail `--dev`
+            Err(err) => {
+                let path = dir.join("package.json");
+                warn!(path = %path.display(), %err, "ignoring invalid package.json");
+            }
+        }
+    }
+    if let Some(content) = read(dir, ".python-version")? {
+        push(".python-version", "python.org", version_file(&content));
+    }
+    for filename in ["rust-toolchain.toml", "rust-toolchain"] {
+        if let Some(content) = read(dir, filename)? {
+            let channel = match CHANNEL_REGEX.captures(&content) {
+                Some(captures) => captures[1].to_string(),
+                // the legacy format is just the channel
+                None => content.trim().to_string(),
+            };
+            push(filename, "rust-lang.org", version_file(&channel));
+        }
+    }
+    if let Some(content) = read(dir, "go.mod")? {
+        // the `go` directive is the minimum supported version
+        let constraint = GO_REGEX
+            .captures(&content)
+            .and_then(|captures| VersionReq::parse(&format!(">={}", &captures[1])).ok());
+        push("go.mod", "go.dev", constraint);
+    }
+    if let Some(content) = read(dir, ".ruby-version")? {
+        let content = content.trim().trim_start_matches("ruby-");
+        push(".ruby-version", "ruby-lang.org", version_file(content));
+    }
+    if let Some(content) = read(dir, ".terraform-version")? {
+        push(".terraform-version", "terraform.io", version_file(&content));
+    }
+
+    Ok(rv)
+}
+
+fn read(dir: &Path, filename: &str) -> Result<Option<String>, Error> {
+    let path = dir.join(filename);
+    if path.is_file() {
+        Ok(Some(fs::read_to_string(path)?))
+    } else {
+        Ok(None)
+    }
+}
+
+/// eg. `v20.11` is `@20.11`, aliases like `lts/*`, `stable` or `system` are `*`
+fn version_file(content: &str) -> Option<VersionReq> {
+    let line = content
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty() && !line.starts_with('#'))?;
+    match VERSION_REGEX.captures(line) {
+        Some(captures) => VersionReq::parse(&format!("@{}", &captures[1])).ok(),
+        None => VersionReq::parse("*").ok(),
+    }
+}
+
+/// npm style ranges, eg. `>=18`, `^20.1.0` or `18.x`
+fn range(input: &str) -> Option<VersionReq> {
+    let input = input.trim().replace(".x", "").replace(".*", "");
+    match input.as_str() {
+        "" | "*" | "x" => VersionReq::parse("*").ok(),
+        input if VERSION_REGEX.is_match(input) => {
+            VersionReq::parse(&format!("@{}", input.trim_start_matches('v'))).ok()
+        }
+        input => VersionReq::parse(input).ok(),
+    }
+}
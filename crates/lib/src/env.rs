@@ -1,128 +1,404 @@
+use crate::Error;
 use std::{
     collections::{HashMap, HashSet},
-    error::Error,
-    path::PathBuf,
-    str::FromStr,
+    fs,
+    path::{Path, PathBuf},
 };
 
-use crate::types::Installation;
+use serde::{Deserialize, Deserializer, Serialize};
+use tracing::{debug, trace};
 
-pub fn map(installations: &Vec<Installation>) -> HashMap<String, Vec<String>> {
-    let mut vars: HashMap<EnvKey, OrderedSet<PathBuf>> = HashMap::new();
+use crate::{
+    moustache::{self, MoustacheError},
+    pantry_db,
+    types::{host, Installation, Package},
+    user_config::UserConfig,
+};
 
-    let projects: HashSet<&str> = installations
-        .iter()
-        .map(|i| i.pkg.project.as_str())
-        .collect();
+/// Which directories of installations go in an environment variable.
+#[derive(Debug, Clone, PartialEq, Default, Serialize)]
+pub struct EnvRule {
+    /// relative to each installation, `*` matches any one path component and
+    /// `.` is the installation itself
+    pub suffixes: Vec<String>,
+    /// appended after the installations’ directories, eg. so we don’t break `man`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub defaults: Vec<String>,
+    /// only if this project is also installed, eg. `cmake.org`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires: Option<String>,
+    /// only for installations of this project
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+    /// only on this platform, eg. `darwin`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    /// a single directory that replaces what the user has rather than a list
+    /// that is prepended to it, eg. `JAVA_HOME`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub replace: bool,
+}
 
-    for installation in installations {
-        for key in EnvKey::iter() {
-            if let Some(suffixes) = suffixes(&key) {
-                for suffix in suffixes {
-                    let path = installation.path.join(suffix);
-                    if path.is_dir() {
-                        vars.entry(key.clone())
-                            .or_insert_with(OrderedSet::new)
-                            .add(path);
-                    }
-                }
-            }
+impl<'de> Deserialize<'de> for EnvRule {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        // `KEY: suffix`, `KEY: [suffixes]` or the full rule
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum EnvRuleHelper {
+            One(String),
+            Many(Vec<String>),
+            Full(FullRule),
         }
 
-        if projects.contains("cmake.org") {
-            vars.entry(EnvKey::CmakePrefixPath)
-                .or_insert_with(OrderedSet::new)
-                .add(installation.path.clone());
+        // so a typo is an error rather than ignored
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct FullRule {
+            suffixes: Vec<String>,
+            #[serde(default)]
+            defaults: Vec<String>,
+            requires: Option<String>,
+            project: Option<String>,
+            platform: Option<String>,
+            #[serde(default)]
+            replace: bool,
         }
+
+        Ok(match EnvRuleHelper::deserialize(deserializer)? {
+            EnvRuleHelper::One(suffix) => EnvRule::new(&[&suffix]),
+            EnvRuleHelper::Many(suffixes) => EnvRule {
+                suffixes,
+                ..Default::default()
+            },
+            EnvRuleHelper::Full(FullRule {
+                suffixes,
+                defaults,
+                requires,
+                project,
+                platform,
+                replace,
+            }) => EnvRule {
+                suffixes,
+                defaults,
+                requires,
+                project,
+                platform,
+                replace,
+            },
+        })
     }
+}
 
-    // don’t break `man`
-    if vars.contains_key(&EnvKey::Manpath) {
-        vars.get_mut(&EnvKey::Manpath)
-            .unwrap()
-            .add(PathBuf::from_str("/usr/share/man").unwrap());
+impl EnvRule {
+    fn new(suffixes: &[&str]) -> Self {
+        EnvRule {
+            suffixes: suffixes.iter().map(|x| x.to_string()).collect(),
+            ..Default::default()
+        }
     }
-    // https://github.com/pkgxdev/libpkgx/issues/70
-    if vars.contains_key(&EnvKey::XdgDataDirs) {
-        let set = vars.get_mut(&EnvKey::XdgDataDirs).unwrap();
-        set.add(PathBuf::from_str("/usr/local/share").unwrap());
-        set.add(PathBuf::from_str("/usr/share").unwrap());
+}
+
+/// The rules we always apply, `rules` adds to these.
+pub fn builtin_rules() -> Vec<(String, EnvRule)> {
+    let requires = |project: &str, suffixes: &[&str]| EnvRule {
+        requires: Some(project.to_string()),
+        ..EnvRule::new(suffixes)
+    };
+    let rules = [
+        ("PATH", EnvRule::new(&["bin", "sbin"])),
+        (
+            "MANPATH",
+            EnvRule {
+                defaults: vec!["/usr/share/man".to_string()],
+                ..EnvRule::new(&["man", "share/man"])
+            },
+        ),
+        (
+            "PKG_CONFIG_PATH",
+            EnvRule::new(&["share/pkgconfig", "lib/pkgconfig"]),
+        ),
+        ("LIBRARY_PATH", EnvRule::new(&["lib", "lib64"])),
+        ("LD_LIBRARY_PATH", EnvRule::new(&["lib", "lib64"])),
+        (
+            "DYLD_FALLBACK_LIBRARY_PATH",
+            EnvRule {
+                platform: Some("darwin".to_string()),
+                ..EnvRule::new(&["lib", "lib64"])
+            },
+        ),
+        ("CPATH", EnvRule::new(&["include"])),
+        (
+            "XDG_DATA_DIRS",
+            // https://github.com/pkgxdev/libpkgx/issues/70
+            EnvRule {
+                defaults: vec!["/usr/local/share".to_string(), "/usr/share".to_string()],
+                ..EnvRule::new(&["share"])
+            },
+        ),
+        ("ACLOCAL_PATH", EnvRule::new(&["share/aclocal"])),
+        ("CMAKE_PREFIX_PATH", requires("cmake.org", &["."])),
+        (
+            "PYTHONPATH",
+            requires("python.org", &["lib/python*/site-packages"]),
+        ),
+        ("PERL5LIB", requires("perl.org", &["lib/perl5"])),
+        ("GEM_PATH", requires("ruby-lang.org", &["lib/ruby/gems/*"])),
+        // pkgs that ship Go sources
+        ("GOPATH", requires("go.dev", &["share/go"])),
+        (
+            "JAVA_HOME",
+            EnvRule {
+                project: Some("openjdk.org".to_string()),
+                replace: true,
+                ..EnvRule::new(&["."])
+            },
+        ),
+    ];
+    rules
+        .into_iter()
+        .map(|(key, rule)| (key.to_string(), rule))
+        .collect()
+}
+
+/// The builtin rules plus those the `installations` declare in the pantry
+/// (`search-paths`) and those in the user’s `config.yml` (`env`).
+pub fn rules(
+    installations: &[Installation],
+    conn: &rusqlite::Connection,
+    user_config: &UserConfig,
+) -> Result<Vec<(String, EnvRule)>, Error> {
+    let mut rv = builtin_rules();
+
+    let projects: Vec<String> = installations
+        .iter()
+        .map(|i| i.pkg.project.clone())
+        .collect();
+    for search_path in pantry_db::search_paths_for_projects(&projects, conn)? {
+        let rule = EnvRule {
+            project: Some(search_path.project),
+            ..EnvRule::new(&[&search_path.suffix])
+        };
+        rv.push((search_path.key, rule));
     }
 
+    rv.extend(user_config.env.clone());
+
+    debug!(count = rv.len(), "env rules");
+    Ok(rv)
+}
+
+/// The directories of `installations` to prepend to each variable, those of
+/// `replace` rules are in `values` instead.
+pub fn map(
+    installations: &[Installation],
+    rules: &[(String, EnvRule)],
+) -> HashMap<String, Vec<String>> {
+    let replaced = replaced(rules);
     let mut rv: HashMap<String, Vec<String>> = HashMap::new();
-    for (key, set) in vars {
-        let set = set
-            .items
-            .iter()
-            .map(|p| p.to_string_lossy().to_string())
-            .collect();
-        rv.insert(key.as_ref().to_string(), set);
+    for contribution in explain_paths(installations, rules) {
+        if replaced.contains(contribution.key.as_str()) {
+            continue;
+        }
+        trace!(
+            key = contribution.key,
+            value = contribution.value,
+            "env path"
+        );
+        rv.entry(contribution.key)
+            .or_default()
+            .push(contribution.value);
     }
     rv
 }
 
-use rusqlite::Connection;
-use strum::IntoEnumIterator;
-use strum_macros::{AsRefStr, EnumIter, EnumString};
-
-#[derive(Debug, EnumString, AsRefStr, PartialEq, Eq, Hash, Clone, EnumIter)]
-#[strum(serialize_all = "SCREAMING_SNAKE_CASE")]
-enum EnvKey {
-    Path,
-    Manpath,
-    PkgConfigPath,
-    LibraryPath,
-    LdLibraryPath,
-    Cpath,
-    XdgDataDirs,
-    CmakePrefixPath,
-    #[cfg(target_os = "macos")]
-    DyldFallbackLibraryPath,
-    SslCertFile,
-    Ldflags,
-    PkgxDir,
-    AclocalPath,
-}
-
-struct OrderedSet<T: Eq + std::hash::Hash + Clone> {
-    items: Vec<T>,
-    set: HashSet<T>,
-}
-
-impl<T: Eq + std::hash::Hash + Clone> OrderedSet<T> {
-    fn new() -> Self {
-        OrderedSet {
-            items: Vec::new(),
-            set: HashSet::new(),
+/// The variables `replace` rules set outright, to the first directory that
+/// matches, eg. `JAVA_HOME`.
+pub fn values(
+    installations: &[Installation],
+    rules: &[(String, EnvRule)],
+) -> HashMap<String, String> {
+    let replaced = replaced(rules);
+    let mut rv = HashMap::new();
+    for contribution in explain_paths(installations, rules) {
+        if replaced.contains(contribution.key.as_str()) {
+            trace!(
+                key = contribution.key,
+                value = contribution.value,
+                "env value"
+            );
+            rv.entry(contribution.key).or_insert(contribution.value);
         }
     }
+    rv
+}
+
+// the keys of `replace` rules
+fn replaced(rules: &[(String, EnvRule)]) -> HashSet<&str> {
+    rules
+        .iter()
+        .filter(|(_, rule)| rule.replace)
+        .map(|(key, _)| key.as_str())
+        .collect()
+}
 
-    fn add(&mut self, item: T) {
-        if self.set.insert(item.clone()) {
-            self.items.push(item);
+/// Where a path or value in the environment came from, see `explain`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Contribution {
+    pub key: String,
+    pub value: String,
+    /// `None` for rule defaults like `/usr/share/man`
+    pub pkg: Option<Package>,
+    pub source: Source,
+}
+
+/// As JSON `{"kind": "suffix", "value": "bin"}` or eg. `{"kind": "default"}`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", content = "value", rename_all = "kebab-case")]
+pub enum Source {
+    /// a directory matched by this `EnvRule` suffix
+    Suffix(String),
+    /// an `EnvRule` default
+    Default,
+    /// the package’s `runtime.env` in the pantry
+    RuntimeEnv,
+}
+
+/// Everything `map` and `mix_runtime` contribute to the environment, in order.
+pub fn explain(
+    installations: &[Installation],
+    rules: &[(String, EnvRule)],
+    conn: &rusqlite::Connection,
+) -> Result<Vec<Contribution>, Error> {
+    let mut rv = explain_paths(installations, rules);
+    for installation in installations {
+        let mut runtime_env: Vec<(String, String)> =
+            pantry_db::runtime_env_for_project(&installation.pkg.project, conn)?
+                .into_iter()
+                .collect();
+        runtime_env.sort();
+        for (key, value) in runtime_env {
+            rv.push(Contribution {
+                key,
+                value: expand_moustaches(&value, installation, installations)
+                    .map_err(|err| Error::pantry(&installation.pkg.project, err))?,
+                pkg: Some(installation.pkg.clone()),
+                source: Source::RuntimeEnv,
+            });
+        }
+    }
+    Ok(rv)
+}
+
+/// The directories `map` puts in each variable and where they came from.
+pub fn explain_paths(
+    installations: &[Installation],
+    rules: &[(String, EnvRule)],
+) -> Vec<Contribution> {
+    let mut rv = vec![];
+    let mut seen: HashSet<(&str, PathBuf)> = HashSet::new();
+
+    let projects: HashSet<&str> = installations
+        .iter()
+        .map(|i| i.pkg.project.as_str())
+        .collect();
+    let platform = host().0.to_string();
+
+    let rules: Vec<&(String, EnvRule)> = rules
+        .iter()
+        .filter(|(_, rule)| match &rule.requires {
+            Some(project) => projects.contains(project.as_str()),
+            None => true,
+        })
+        .filter(|(_, rule)| rule.platform.as_ref().is_none_or(|x| *x == platform))
+        .collect();
+
+    for installation in installations {
+        for (key, rule) in &rules {
+            if let Some(project) = &rule.project {
+                if *project != installation.pkg.project {
+                    continue;
+                }
+            }
+            for suffix in &rule.suffixes {
+                for path in glob(&installation.path, suffix) {
+                    if seen.insert((key, path.clone())) {
+                        rv.push(Contribution {
+                            key: key.clone(),
+                            value: path.to_string_lossy().to_string(),
+                            pkg: Some(installation.pkg.clone()),
+                            source: Source::Suffix(suffix.clone()),
+                        });
+                    }
+                }
+            }
         }
     }
+
+    for (key, rule) in &rules {
+        // defaults only make sense after something else
+        if !seen.iter().any(|(k, _)| k == key) {
+            continue;
+        }
+        for path in &rule.defaults {
+            if seen.insert((key, PathBuf::from(path))) {
+                rv.push(Contribution {
+                    key: key.clone(),
+                    value: path.clone(),
+                    pkg: None,
+                    source: Source::Default,
+                });
+            }
+        }
+    }
+
+    rv
 }
 
-fn suffixes(key: &EnvKey) -> Option<Vec<&'static str>> {
-    match key {
-        EnvKey::Path => Some(vec!["bin", "sbin"]),
-        EnvKey::Manpath => Some(vec!["man", "share/man"]),
-        EnvKey::PkgConfigPath => Some(vec!["share/pkgconfig", "lib/pkgconfig"]),
-        EnvKey::XdgDataDirs => Some(vec!["share"]),
-        EnvKey::AclocalPath => Some(vec!["share/aclocal"]),
-        EnvKey::LibraryPath | EnvKey::LdLibraryPath => Some(vec!["lib", "lib64"]),
-        #[cfg(target_os = "macos")]
-        EnvKey::DyldFallbackLibraryPath => Some(vec!["lib", "lib64"]),
-        EnvKey::Cpath => Some(vec!["include"]),
-        EnvKey::CmakePrefixPath | EnvKey::SslCertFile | EnvKey::Ldflags | EnvKey::PkgxDir => None,
+// the directories under `root` matching `pattern`, sorted
+fn glob(root: &Path, pattern: &str) -> Vec<PathBuf> {
+    let mut rv = vec![root.to_path_buf()];
+    for component in pattern.split('/').filter(|x| !x.is_empty() && *x != ".") {
+        rv = match component.split_once('*') {
+            None => rv.into_iter().map(|dir| dir.join(component)).collect(),
+            Some((prefix, suffix)) => rv
+                .into_iter()
+                .flat_map(|dir| {
+                    let mut matches: Vec<PathBuf> = fs::read_dir(&dir)
+                        .into_iter()
+                        .flatten()
+                        .flatten()
+                        .filter(|entry| {
+                            let name = entry.file_name().to_string_lossy().to_string();
+                            name.len() >= prefix.len() + suffix.len()
+                                && name.starts_with(prefix)
+                                && name.ends_with(suffix)
+                        })
+                        .map(|entry| entry.path())
+                        .collect();
+                    matches.sort();
+                    matches
+                })
+                .collect(),
+        };
     }
+    rv.into_iter().filter(|path| path.is_dir()).collect()
 }
 
 pub fn mix(input: HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    mix_into(std::env::vars(), input)
+}
+
+// `mix` for `vars` rather than our environment
+pub(crate) fn mix_into(
+    vars: impl IntoIterator<Item = (String, String)>,
+    input: HashMap<String, Vec<String>>,
+) -> HashMap<String, String> {
     let mut rv = HashMap::new();
 
-    for (key, mut value) in std::env::vars() {
+    for (key, mut value) in vars {
         if let Some(injected_values) = input.get(&key) {
             value = format!("{}:{}", injected_values.join(":"), value);
         }
@@ -132,79 +408,285 @@ pub fn mix(input: HashMap<String, Vec<String>>) -> HashMap<String, String> {
     rv
 }
 
+/// inherited by `pure` environments, everything else comes from pkgx
+pub const ALLOWLIST: &[&str] = &[
+    "HOME",
+    "USER",
+    "LOGNAME",
+    "TERM",
+    "COLORTERM",
+    "LANG",
+    "TZ",
+    "TMPDIR",
+    "DISPLAY",
+    "SSH_AUTH_SOCK",
+];
+
+/// The environment we run in, or for `pure` only what the `ALLOWLIST` lets through.
+pub fn inherited(pure: bool) -> HashMap<String, String> {
+    keep(std::env::vars(), pure)
+}
+
+// what `inherited` keeps of `vars`
+pub(crate) fn keep(
+    vars: impl IntoIterator<Item = (String, String)>,
+    pure: bool,
+) -> HashMap<String, String> {
+    vars.into_iter()
+        .filter(|(key, _)| !pure || ALLOWLIST.contains(&key.as_str()) || key.starts_with("LC_"))
+        .collect()
+}
+
+/// Like `mix` but starts from scratch rather than the inherited environment,
+/// so anything not provided by pkgx (or the `ALLOWLIST`) is missing.
+pub fn pure(input: HashMap<String, Vec<String>>) -> HashMap<String, String> {
+    overlay(inherited(true), input)
+}
+
+// `base` with `input` replacing what it has
+pub(crate) fn overlay(
+    mut base: HashMap<String, String>,
+    input: HashMap<String, Vec<String>>,
+) -> HashMap<String, String> {
+    for (key, value) in input {
+        base.insert(key, value.join(":"));
+    }
+    base
+}
+
+/// Adds the pantry’s runtime env of `installations` to `input`, variables they
+/// reference that we haven’t computed come from `base`, see `inherited`.
 pub fn mix_runtime(
     input: &HashMap<String, String>,
-    installations: &Vec<Installation>,
-    conn: &Connection,
-) -> Result<HashMap<String, String>, Box<dyn Error>> {
+    base: &HashMap<String, String>,
+    installations: &[Installation],
+    conn: &rusqlite::Connection,
+) -> Result<HashMap<String, String>, Error> {
     let mut output = input.clone();
 
-    for installation in installations.clone() {
-        let runtime_env =
-            crate::pantry_db::runtime_env_for_project(&installation.pkg.project, conn)?;
-        for (key, runtime_value) in runtime_env {
-            let runtime_value = expand_moustaches(&runtime_value, &installation, installations);
-            let new_value = match output.get(&key) {
-                Some(curr_value) => runtime_value.replace(&format!("${}", key), curr_value),
+    for installation in installations {
+        let mut pass: HashMap<String, String> =
+            crate::pantry_db::runtime_env_for_project(&installation.pkg.project, conn)?
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = expand_moustaches(&value, installation, installations)?;
+                    Ok((key, value))
+                })
+                .collect::<Result<_, MoustacheError>>()
+                .map_err(|err| Error::pantry(&installation.pkg.project, err))?;
+        let mut keys: Vec<String> = pass.keys().cloned().collect();
+        keys.sort();
+        for key in keys {
+            resolve(&key, &mut pass, &mut output, base);
+            trace!(pkg = %installation.pkg, key, value = output[&key], "runtime env");
+        }
+    }
+
+    Ok(output)
+}
+
+// sets `key` from this `pass`, after any other keys of the pass it references
+fn resolve(
+    key: &str,
+    pass: &mut HashMap<String, String>,
+    output: &mut HashMap<String, String>,
+    base: &HashMap<String, String>,
+) {
+    // removing it first means cycles see the previous value rather than recursing forever
+    let template = match pass.remove(key) {
+        Some(template) => template,
+        None => return,
+    };
+
+    let mut references = vec![];
+    expand(&template, &mut |name| {
+        references.push(name.to_string());
+        None
+    });
+    for name in references.iter().filter(|name| *name != key) {
+        resolve(name, pass, output, base);
+    }
+
+    let value = expand(&template, &mut |name| {
+        output.get(name).cloned().or_else(|| {
+            // `$KEY` refers to what we have computed, but others may come from the user
+            (name != key).then(|| base.get(name).cloned()).flatten()
+        })
+    });
+    output.insert(key.to_string(), value);
+}
+
+/// Expands `$VAR`, `${VAR}` and `${VAR:-default}` in `template` via `lookup`.
+///
+/// Unset variables expand to nothing and `:` separated segments that expand
+/// to nothing are removed, eg. `/foo:$PATH` is `/foo` if `PATH` is unset.
+pub fn expand<F>(template: &str, lookup: &mut F) -> String
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    split_list(template)
+        .into_iter()
+        .filter_map(|segment| {
+            let (value, substituted) = expand_segment(segment, lookup);
+            if value.is_empty() && substituted {
+                None
+            } else {
+                Some(value)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+// splits on `:` except inside `${…}` (where it may start a `:-default`)
+fn split_list(template: &str) -> Vec<&str> {
+    let mut rv = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    let bytes = template.as_bytes();
+    for (i, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'{' if i > 0 && bytes[i - 1] == b'$' => depth += 1,
+            b'}' if depth > 0 => depth -= 1,
+            b':' if depth == 0 => {
+                rv.push(&template[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    rv.push(&template[start..]);
+    rv
+}
+
+// returns the expansion and whether it substituted any variables
+fn expand_segment<F>(segment: &str, lookup: &mut F) -> (String, bool)
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut rv = String::new();
+    let mut substituted = false;
+    let mut rest = segment;
+
+    while let Some(i) = rest.find('$') {
+        rv.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if let Some(braced) = rest.strip_prefix('{') {
+            let end = match closing_brace(braced) {
+                Some(end) => end,
                 None => {
-                    //TODO need to remove any $FOO, aware of `:` delimiters
-                    runtime_value
-                        .replace(&format!(":${}", key), "")
-                        .replace(&format!("${}:", key), "")
-                        .replace(&format!("${}", key), "")
+                    rv.push('$');
+                    continue;
                 }
             };
-            output.insert(key, new_value);
+            let inner = &braced[..end];
+            let (name, default) = match inner.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (inner, None),
+            };
+            if !is_name(name) {
+                rv.push('$');
+                continue;
+            }
+            let value = lookup(name).filter(|value| !value.is_empty());
+            match (value, default) {
+                (Some(value), _) => rv.push_str(&value),
+                (None, Some(default)) => rv.push_str(&expand(default, lookup)),
+                (None, None) => (),
+            }
+            substituted = true;
+            rest = &braced[end + 1..];
+        } else {
+            let len = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            let name = &rest[..len];
+            if !is_name(name) {
+                rv.push('$');
+                continue;
+            }
+            if let Some(value) = lookup(name) {
+                rv.push_str(&value);
+            }
+            substituted = true;
+            rest = &rest[len..];
         }
     }
+    rv.push_str(rest);
 
-    Ok(output)
+    (rv, substituted)
 }
 
-pub fn expand_moustaches(input: &str, pkg: &Installation, deps: &Vec<Installation>) -> String {
-    let mut output = input.to_string();
-
-    if output.starts_with("${{") {
-        output.replace_range(..1, "");
+// the index of the `}` that closes a `${`, allowing for nested `${…}`
+fn closing_brace(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    let bytes = input.as_bytes();
+    for (i, byte) in bytes.iter().enumerate() {
+        match byte {
+            b'{' if i > 0 && bytes[i - 1] == b'$' => depth += 1,
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => (),
+        }
     }
+    None
+}
+
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
 
-    output = output.replace("{{prefix}}", &pkg.path.to_string_lossy());
-    output = output.replace("{{version}}", &format!("{}", &pkg.pkg.version));
-    output = output.replace("{{version.major}}", &format!("{}", pkg.pkg.version.major));
-    output = output.replace("{{version.minor}}", &format!("{}", pkg.pkg.version.minor));
-    output = output.replace("{{version.patch}}", &format!("{}", pkg.pkg.version.patch));
-    output = output.replace(
-        "{{version.marketing}}",
-        &format!("{}.{}", pkg.pkg.version.major, pkg.pkg.version.minor),
-    );
+/// Renders the moustaches pantry runtime values may contain for `pkg`.
+pub fn expand_moustaches(
+    input: &str,
+    pkg: &Installation,
+    deps: &[Installation],
+) -> Result<String, MoustacheError> {
+    moustache::render(input, &moustaches(pkg, deps))
+}
 
-    for dep in deps {
-        let prefix = format!("deps.{}", dep.pkg.project);
-        output = output.replace(
-            &format!("{{{{{}.prefix}}}}", prefix),
-            &dep.path.to_string_lossy(),
-        );
-        output = output.replace(
-            &format!("{{{{{}.version}}}}", prefix),
-            &format!("{}", &dep.pkg.version),
-        );
-        output = output.replace(
-            &format!("{{{{{}.version.major}}}}", prefix),
-            &format!("{}", dep.pkg.version.major),
-        );
-        output = output.replace(
-            &format!("{{{{{}.version.minor}}}}", prefix),
-            &format!("{}", dep.pkg.version.minor),
-        );
-        output = output.replace(
-            &format!("{{{{{}.version.patch}}}}", prefix),
-            &format!("{}", dep.pkg.version.patch),
-        );
-        output = output.replace(
-            &format!("{{{{{}.version.marketing}}}}", prefix),
-            &format!("{}.{}", dep.pkg.version.major, dep.pkg.version.minor),
+fn moustaches(pkg: &Installation, deps: &[Installation]) -> HashMap<String, String> {
+    let mut rv = HashMap::new();
+
+    let (platform, arch) = host();
+    rv.insert("hw.platform".to_string(), platform.to_string());
+    rv.insert("hw.arch".to_string(), arch.to_string());
+    if let Some(home) = dirs_next::home_dir() {
+        rv.insert("home".to_string(), home.to_string_lossy().to_string());
+    }
+    // installations live at `{{pkgx.dir}}/<project>/v<version>`
+    let depth = pkg.pkg.project.split('/').count() + 1;
+    if let Some(pkgx_dir) = pkg.path.ancestors().nth(depth) {
+        rv.insert(
+            "pkgx.dir".to_string(),
+            pkgx_dir.to_string_lossy().to_string(),
         );
     }
 
-    output
+    for (prefix, installation) in deps
+        .iter()
+        .map(|dep| (format!("deps.{}.", dep.pkg.project), dep))
+        .chain(std::iter::once((String::new(), pkg)))
+    {
+        let version = &installation.pkg.version;
+        let vars = [
+            ("prefix", installation.path.to_string_lossy().to_string()),
+            ("version", version.to_string()),
+            ("version.major", version.major.to_string()),
+            ("version.minor", version.minor.to_string()),
+            ("version.patch", version.patch.to_string()),
+            (
+                "version.marketing",
+                format!("{}.{}", version.major, version.minor),
+            ),
+        ];
+        for (key, value) in vars {
+            rv.insert(format!("{}{}", prefix, key), value);
+        }
+    }
+
+    rv
 }
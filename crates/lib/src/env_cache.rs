@@ -0,0 +1,108 @@
+use crate::config::Config;
+use crate::manifest::Manifest;
+use crate::shell::Change;
+use crate::types::Installation;
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use tracing::debug;
+
+// computing an env means syncing, resolving and hitting the network, which is
+// too slow for eg. a shell’s directory hook, so we cache the result
+
+// the least recently written entries beyond this are removed
+const MAX_ENTRIES: usize = 256;
+
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    /// if any of these are gone the cache is stale
+    installations: Vec<PathBuf>,
+    changes: Vec<(String, Change)>,
+}
+
+/// Identifies a manifest’s contents: its files, their mtimes and its deps.
+/// Stable across builds of pkgx.
+pub fn key(manifest: &Manifest) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    for path in &manifest.paths {
+        let mtime = fs::metadata(path)?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        hasher.update(path.as_os_str().as_bytes());
+        hasher.update([0]);
+        hasher.update(mtime.as_nanos().to_le_bytes());
+    }
+    for dep in &manifest.deps {
+        hasher.update(dep.to_string());
+        hasher.update([0]);
+    }
+    Ok(format!("{:x}", hasher.finalize())[..16].to_string())
+}
+
+pub fn get(key: &str, config: &Config) -> Option<Vec<(String, Change)>> {
+    let Ok(content) = fs::read_to_string(path(key, config)) else {
+        debug!(key, "env cache miss");
+        return None;
+    };
+    let entry: Entry = serde_json::from_str(&content).ok()?;
+    if entry.installations.iter().all(|path| path.is_dir()) {
+        debug!(key, "env cache hit");
+        Some(entry.changes)
+    } else {
+        debug!(key, "env cache stale");
+        None
+    }
+}
+
+pub fn put(
+    key: &str,
+    installations: &[Installation],
+    changes: &[(String, Change)],
+    config: &Config,
+) -> Result<(), Error> {
+    let entry = Entry {
+        installations: installations.iter().map(|i| i.path.clone()).collect(),
+        changes: changes.to_vec(),
+    };
+    let path = path(key, config);
+    fs::create_dir_all(path.parent().unwrap())?;
+    fs::write(
+        &path,
+        serde_json::to_string(&entry).map_err(std::io::Error::from)?,
+    )?;
+    prune(path.parent().unwrap(), MAX_ENTRIES)
+}
+
+/// Removes all but the `max` most recently written entries in `dir`.
+pub(crate) fn prune(dir: &Path, max: usize) -> Result<(), Error> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(dir)?.flatten() {
+        // another pkgx may be pruning too
+        if let Ok(mtime) = entry.metadata().and_then(|metadata| metadata.modified()) {
+            entries.push((mtime, entry.path()));
+        }
+    }
+    if entries.len() <= max {
+        return Ok(());
+    }
+    entries.sort();
+    for (_, path) in &entries[..entries.len() - max] {
+        debug!(path = %path.display(), "pruning env cache");
+        let _ = fs::remove_file(path);
+    }
+    Ok(())
+}
+
+fn path(key: &str, config: &Config) -> PathBuf {
+    config
+        .pantry_dir
+        .parent()
+        .unwrap()
+        .join("envs")
+        .join(format!("{}.json", key))
+}
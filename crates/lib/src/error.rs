@@ -0,0 +1,160 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::{types::Package, which::WhichError};
+
+/// What went wrong, `code()` identifies the kind for front-ends.
+#[derive(Debug)]
+pub enum Error {
+    /// no such pkg, version, program or installation
+    NotFound(String),
+    /// requirements that can’t all be satisfied, or a program several pkgs provide
+    Conflict(String),
+    /// `status` is `None` if there was no response, `transient` if the
+    /// connection failed or timed out, or the body was cut short
+    Network {
+        url: String,
+        status: Option<u16>,
+        reason: String,
+        transient: bool,
+    },
+    /// a download that isn’t what its checksum says it should be
+    Checksum {
+        url: String,
+    },
+    /// another pkgx holds the lock
+    Lock {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// the pantry has an entry we can’t use, `path` is relative to the pantry
+    Pantry {
+        path: PathBuf,
+        reason: String,
+    },
+    /// a pkgspec, manifest or config the user gave us is malformed
+    Invalid(String),
+    Io(std::io::Error),
+    Db(rusqlite::Error),
+    /// several pkgs failed to install, `code()` is the first’s
+    Install(Vec<(Package, Error)>),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    /// Stable identifiers, eg. for exit codes or `--json` output.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::NotFound(_) => "not-found",
+            Error::Conflict(_) => "conflict",
+            Error::Network { .. } => "network",
+            Error::Checksum { .. } => "checksum",
+            Error::Lock { .. } => "lock",
+            Error::Pantry { .. } => "pantry",
+            Error::Invalid(_) => "invalid",
+            Error::Io(_) => "io",
+            Error::Db(_) => "db",
+            Error::Install(failures) => failures.first().map_or("io", |(_, err)| err.code()),
+        }
+    }
+
+    /// Connection failures, timeouts, bodies cut short and 5xx responses,
+    /// worth trying again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Network {
+                status, transient, ..
+            } => *transient || status.is_some_and(|status| status >= 500),
+            _ => false,
+        }
+    }
+
+    /// `projects/foo.com/package.yml`
+    pub(crate) fn pantry(project: &str, reason: impl fmt::Display) -> Self {
+        Error::Pantry {
+            path: PathBuf::from("projects").join(project).join("package.yml"),
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NotFound(what) => write!(f, "not found: {}", what),
+            Error::Conflict(reason) => write!(f, "{}", reason),
+            Error::Network {
+                url,
+                status: Some(status),
+                ..
+            } => write!(f, "couldn’t download {} (HTTP {})", url, status),
+            Error::Network { url, reason, .. } => {
+                write!(f, "couldn’t download {}: {}", url, reason)
+            }
+            Error::Checksum { url } => write!(f, "checksum mismatch: {}", url),
+            Error::Lock { path, source } => {
+                write!(f, "couldn’t lock {}: {}", path.display(), source)
+            }
+            Error::Pantry { path, reason } => {
+                write!(f, "invalid pantry entry: {}: {}", path.display(), reason)
+            }
+            Error::Invalid(reason) => write!(f, "{}", reason),
+            Error::Io(err) => write!(f, "{}", err),
+            Error::Db(err) => write!(f, "pkg-db: {}", err),
+            Error::Install(failures) => {
+                write!(f, "couldn’t install {} pkgs:", failures.len())?;
+                for (pkg, err) in failures {
+                    write!(f, "\n  {}: {}", pkg, err)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Lock { source, .. } => Some(source),
+            Error::Io(err) => Some(err),
+            Error::Db(err) => Some(err),
+            Error::Install(failures) => failures.first().map(|(_, err)| err as _),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Db(err)
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Network {
+            url: err.url().map(|url| url.to_string()).unwrap_or_default(),
+            status: err.status().map(|status| status.as_u16()),
+            transient: err.is_connect() || err.is_timeout() || err.is_body(),
+            // the url is in our message
+            reason: err.without_url().to_string(),
+        }
+    }
+}
+
+impl From<WhichError> for Error {
+    fn from(err: WhichError) -> Self {
+        match err {
+            WhichError::CmdNotFound(cmd) => Error::NotFound(cmd),
+            WhichError::MultipleProjects(..) => Error::Conflict(err.to_string()),
+            WhichError::DbError(err) => Error::Db(err),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use serde::Serialize;
+use tokio::sync::mpsc;
+
+use crate::types::Package;
+
+/// The version of `Event`’s JSON, bumped when it changes incompatibly.
+pub const SCHEMA: u32 = 1;
+
+/// What pkgx is doing, for front-ends to show progress.
+///
+/// As JSON the variant is `event`, eg. `{"event": "chunk", "pkg": …, "n": 1024}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event {
+    /// updating the pkg-db
+    Syncing,
+    /// choosing versions for the pkgs and their deps
+    Resolving,
+    /// already installed so there’s nothing to download
+    Cached {
+        pkg: Package,
+    },
+    /// `size` is `None` if the server didn’t say
+    DownloadStarted {
+        pkg: Package,
+        size: Option<u64>,
+    },
+    /// `n` more bytes downloaded
+    Chunk {
+        pkg: Package,
+        n: u64,
+    },
+    /// downloaded, the rest of the extraction follows. Small bottles unpack as
+    /// they download so for them it is nearly done.
+    Extracting {
+        pkg: Package,
+    },
+    Installed {
+        pkg: Package,
+    },
+    Failed {
+        pkg: Package,
+        err: String,
+    },
+}
+
+pub type Sender = mpsc::UnboundedSender<Event>;
+pub type Receiver = mpsc::UnboundedReceiver<Event>;
+
+pub fn channel() -> (Sender, Receiver) {
+    mpsc::unbounded_channel()
+}
+
+// nobody listening isn’t our problem
+pub(crate) fn send(events: Option<&Sender>, event: Event) {
+    if let Some(events) = events {
+        let _ = events.send(event);
+    }
+}
@@ -0,0 +1,282 @@
+//! The C API, `include/pkgx.h` is generated from this by `build.rs`.
+//!
+//! Functions return a `PkgxStatus` and write their results, JSON encoded, to
+//! `out`. Strings we return must be freed with `pkgx_string_free`. If a call
+//! fails `pkgx_last_error` describes why. A null `handle` or `out` is
+//! `PkgxStatus::Invalid`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use serde_json::{json, Value};
+use tokio::runtime::Runtime;
+
+use crate::{config::Config, types::PackageReq, Error, Pkgx};
+
+/// `Error::code()` for C
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PkgxStatus {
+    Ok = 0,
+    NotFound = 1,
+    Conflict = 2,
+    Network = 3,
+    Checksum = 4,
+    Lock = 5,
+    Pantry = 6,
+    Invalid = 7,
+    Io = 8,
+    Db = 9,
+    /// a bug in libpkgx, the handle should not be used again
+    Panic = 10,
+}
+
+impl From<&Error> for PkgxStatus {
+    fn from(err: &Error) -> Self {
+        match err {
+            Error::NotFound(_) => PkgxStatus::NotFound,
+            Error::Conflict(_) => PkgxStatus::Conflict,
+            Error::Network { .. } => PkgxStatus::Network,
+            Error::Checksum { .. } => PkgxStatus::Checksum,
+            Error::Lock { .. } => PkgxStatus::Lock,
+            Error::Pantry { .. } => PkgxStatus::Pantry,
+            Error::Invalid(_) => PkgxStatus::Invalid,
+            Error::Io(_) => PkgxStatus::Io,
+            Error::Db(_) => PkgxStatus::Db,
+            Error::Install(failures) => match failures.first() {
+                Some((_, err)) => PkgxStatus::from(err),
+                None => PkgxStatus::Io,
+            },
+        }
+    }
+}
+
+/// Opaque, from `pkgx_new`. Not safe to use from several threads at once.
+pub struct PkgxHandle {
+    pkgx: Pkgx,
+    runtime: Runtime,
+}
+
+impl PkgxHandle {
+    pub(crate) fn new(pkgx: Pkgx) -> Result<Self, Error> {
+        let runtime = Runtime::new()?;
+        Ok(PkgxHandle { pkgx, runtime })
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// Creates a handle configured like the `pkgx` command, ie. via `PKGX_DIR` etc.
+/// On failure `out` is set to null.
+///
+/// # Safety
+///
+/// `out` must be valid for writes. Free the handle with `pkgx_free`.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_new(out: *mut *mut PkgxHandle) -> PkgxStatus {
+    if out.is_null() {
+        return invalid("null out");
+    }
+    *out = ptr::null_mut();
+    guard(|| {
+        let handle = PkgxHandle::new(Pkgx::new(Config::new()?)?)?;
+        *out = Box::into_raw(Box::new(handle));
+        Ok(())
+    })
+}
+
+/// # Safety
+///
+/// `handle` must be from `pkgx_new` (or null) and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_free(handle: *mut PkgxHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Updates the pkg-db, the other functions do this if it is missing or outdated.
+///
+/// # Safety
+///
+/// `handle` must be from `pkgx_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_sync(handle: *mut PkgxHandle) -> PkgxStatus {
+    let Some(handle) = handle.as_mut() else {
+        return invalid("null handle");
+    };
+    guard(|| handle.runtime.block_on(handle.pkgx.sync()))
+}
+
+/// The pkg that provides `cmd`: `{"project": "nodejs.org", "path": "bin/node"}`.
+///
+/// # Safety
+///
+/// `handle` must be from `pkgx_new`, `cmd` a C string and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_which(
+    handle: *mut PkgxHandle,
+    cmd: *const c_char,
+    out: *mut *mut c_char,
+) -> PkgxStatus {
+    let Some(handle) = handle.as_mut() else {
+        return invalid("null handle");
+    };
+    json_guard(out, || {
+        let cmd = string(cmd)?;
+        handle.runtime.block_on(handle.pkgx.sync_if_needed())?;
+        let provider = handle.pkgx.which(&cmd)?;
+        Ok(json!({ "project": provider.project, "path": provider.path }))
+    })
+}
+
+/// Resolves whitespace separated `pkgspecs` (eg. `node@20 openssl.org`) and
+/// their deps: `{"pkgs": [{"project": …, "version": …}], "installed": […], "pending": […]}`.
+///
+/// # Safety
+///
+/// `handle` must be from `pkgx_new`, `pkgspecs` a C string and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_resolve(
+    handle: *mut PkgxHandle,
+    pkgspecs: *const c_char,
+    out: *mut *mut c_char,
+) -> PkgxStatus {
+    let Some(handle) = handle.as_mut() else {
+        return invalid("null handle");
+    };
+    json_guard(out, || {
+        let reqs = reqs(handle, pkgspecs)?;
+        let resolution = handle.runtime.block_on(handle.pkgx.resolve(&reqs))?;
+        Ok(json!({
+            "pkgs": resolution.pkgs,
+            "installed": resolution.installed,
+            "pending": resolution.pending,
+        }))
+    })
+}
+
+/// Like `pkgx_resolve` but installs what’s pending, outputs every installation:
+/// `[{"path": …, "project": …, "version": …}]`.
+///
+/// # Safety
+///
+/// `handle` must be from `pkgx_new`, `pkgspecs` a C string and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_install(
+    handle: *mut PkgxHandle,
+    pkgspecs: *const c_char,
+    out: *mut *mut c_char,
+) -> PkgxStatus {
+    let Some(handle) = handle.as_mut() else {
+        return invalid("null handle");
+    };
+    json_guard(out, || {
+        let reqs = reqs(handle, pkgspecs)?;
+        let pkgx = &handle.pkgx;
+        let installations = handle.runtime.block_on(async {
+            let resolution = pkgx.resolve(&reqs).await?;
+            pkgx.install(&resolution).await
+        })?;
+        Ok(json!(installations))
+    })
+}
+
+/// Like `pkgx_install` but outputs the environment to run things in:
+/// `{"PATH": "…", …}`.
+///
+/// # Safety
+///
+/// `handle` must be from `pkgx_new`, `pkgspecs` a C string and `out` valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_env(
+    handle: *mut PkgxHandle,
+    pkgspecs: *const c_char,
+    out: *mut *mut c_char,
+) -> PkgxStatus {
+    let Some(handle) = handle.as_mut() else {
+        return invalid("null handle");
+    };
+    json_guard(out, || {
+        let reqs = reqs(handle, pkgspecs)?;
+        let pkgx = &handle.pkgx;
+        let installations = handle.runtime.block_on(async {
+            let resolution = pkgx.resolve(&reqs).await?;
+            pkgx.install(&resolution).await
+        })?;
+        Ok(json!(pkgx.env(&installations)?))
+    })
+}
+
+/// Why the last call on this thread failed, valid until the next call.
+#[no_mangle]
+pub extern "C" fn pkgx_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// # Safety
+///
+/// `string` must be from libpkgx (or null) and not used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn pkgx_string_free(string: *mut c_char) {
+    if !string.is_null() {
+        drop(CString::from_raw(string));
+    }
+}
+
+// runs `f` recording any error or panic for `pkgx_last_error`
+fn guard<F>(f: F) -> PkgxStatus
+where
+    F: FnOnce() -> Result<(), Error>,
+{
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => (PkgxStatus::Ok, String::new()),
+        Ok(Err(err)) => (PkgxStatus::from(&err), err.to_string()),
+        Err(_) => (PkgxStatus::Panic, "libpkgx panicked".to_string()),
+    };
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = message);
+    status
+}
+
+fn invalid(message: &str) -> PkgxStatus {
+    guard(|| Err(Error::Invalid(message.to_string())))
+}
+
+unsafe fn json_guard<F>(out: *mut *mut c_char, f: F) -> PkgxStatus
+where
+    F: FnOnce() -> Result<Value, Error>,
+{
+    if out.is_null() {
+        return invalid("null out");
+    }
+    *out = ptr::null_mut();
+    guard(|| {
+        let json = CString::new(f()?.to_string()).map_err(|err| Error::Invalid(err.to_string()))?;
+        *out = json.into_raw();
+        Ok(())
+    })
+}
+
+unsafe fn string(ptr: *const c_char) -> Result<String, Error> {
+    if ptr.is_null() {
+        return Err(Error::Invalid("null string".to_string()));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(|s| s.to_string())
+        .map_err(|err| Error::Invalid(err.to_string()))
+}
+
+unsafe fn reqs(handle: &mut PkgxHandle, pkgspecs: *const c_char) -> Result<Vec<PackageReq>, Error> {
+    let pkgspecs = string(pkgspecs)?;
+    handle.runtime.block_on(handle.pkgx.sync_if_needed())?;
+    pkgspecs
+        .split_whitespace()
+        .map(|pkgspec| handle.pkgx.pkgreq(pkgspec))
+        .collect()
+}
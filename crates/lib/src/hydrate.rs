@@ -1,7 +1,9 @@
 use crate::types::PackageReq;
+use crate::Error;
 use libsemverator::range::Range as VersionReq;
 use std::collections::{HashMap, HashSet};
-use std::error::Error;
+use std::time::Instant;
+use tracing::{debug, trace};
 
 #[derive(Clone)]
 struct Node {
@@ -31,14 +33,12 @@ impl Node {
 }
 
 /// Hydrates dependencies and returns a topologically sorted list of packages.
-pub async fn hydrate<F>(
-    input: &Vec<PackageReq>,
-    get_deps: F,
-) -> Result<Vec<PackageReq>, Box<dyn Error>>
+pub async fn hydrate<F>(input: &Vec<PackageReq>, get_deps: F) -> Result<Vec<PackageReq>, Error>
 where
-    F: Fn(String) -> Result<Vec<PackageReq>, Box<dyn Error>>,
+    F: Fn(String) -> Result<Vec<PackageReq>, Error>,
 {
-    let dry = condense(input);
+    let start = Instant::now();
+    let dry = condense(input)?;
     let mut graph: HashMap<String, Box<Node>> = HashMap::new();
     let mut stack: Vec<Box<Node>> = vec![];
     let mut additional_unicodes: Vec<VersionReq> = vec![];
@@ -47,17 +47,18 @@ where
         let node = graph
             .entry(pkg.project.clone())
             .or_insert_with(|| Box::new(Node::new(pkg.clone(), None)));
-        node.pkg.constraint = intersect_constraints(&node.pkg.constraint, &pkg.constraint)?;
+        node.pkg.constraint = intersect_constraints(&node.pkg, &pkg.constraint)?;
         stack.push(node.clone());
     }
 
     while let Some(mut current) = stack.pop() {
-        for child_pkg in get_deps(current.pkg.project.clone())? {
+        let deps = get_deps(current.pkg.project.clone())?;
+        trace!(pkg = %current.pkg, deps = deps.len(), "hydrating");
+        for child_pkg in deps {
             let child_node = graph
                 .entry(child_pkg.project.clone())
                 .or_insert_with(|| Box::new(Node::new(child_pkg.clone(), Some(current.clone()))));
-            let intersection =
-                intersect_constraints(&child_node.pkg.constraint, &child_pkg.constraint);
+            let intersection = intersect_constraints(&child_node.pkg, &child_pkg.constraint);
             if let Ok(constraint) = intersection {
                 child_node.pkg.constraint = constraint;
                 current.children.insert(child_node.pkg.project.clone());
@@ -86,24 +87,29 @@ where
         pkgs.push(pkg);
     }
 
+    debug!(count = pkgs.len(), elapsed = ?start.elapsed(), "hydrated");
     Ok(pkgs)
 }
 
 /// Condenses a list of `PackageRequirement` by intersecting constraints for duplicates.
-fn condense(pkgs: &Vec<PackageReq>) -> Vec<PackageReq> {
+fn condense(pkgs: &Vec<PackageReq>) -> Result<Vec<PackageReq>, Error> {
     let mut out: Vec<PackageReq> = vec![];
     for pkg in pkgs {
         if let Some(existing) = out.iter_mut().find(|p| p.project == pkg.project) {
-            existing.constraint = intersect_constraints(&existing.constraint, &pkg.constraint)
-                .expect("Failed to intersect constraints");
+            existing.constraint = intersect_constraints(existing, &pkg.constraint)?;
         } else {
             out.push(pkg.clone());
         }
     }
-    out
+    Ok(out)
 }
 
-/// Intersects two version constraints.
-fn intersect_constraints(a: &VersionReq, b: &VersionReq) -> Result<VersionReq, Box<dyn Error>> {
-    a.intersect(b).map_err(|e| e.into())
+/// Intersects the requirement’s constraint with another for the same project.
+fn intersect_constraints(a: &PackageReq, b: &VersionReq) -> Result<VersionReq, Error> {
+    a.constraint.intersect(b).map_err(|_| {
+        Error::Conflict(format!(
+            "{}: {} and {} can’t both be satisfied",
+            a.project, a.constraint, b
+        ))
+    })
 }
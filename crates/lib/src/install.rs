@@ -1,9 +1,21 @@
+use crate::Error;
 use async_compression::tokio::bufread::XzDecoder;
 use fs2::FileExt;
-use reqwest::Client;
-use std::{error::Error, fs::OpenOptions, path::PathBuf};
+use reqwest::{
+    header::{CONTENT_RANGE, ETAG, IF_RANGE, LAST_MODIFIED, RANGE},
+    Client, Response, StatusCode,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::OpenOptions,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::io::{AsyncBufRead, AsyncReadExt, AsyncWriteExt, BufReader};
 use tokio::task;
 use tokio_tar::Archive;
+use tracing::{debug, trace};
 
 // Compatibility trait lets us call `compat()` on a futures::io::AsyncRead
 // to convert it into a tokio::io::AsyncRead.
@@ -13,88 +25,315 @@ use tokio_util::compat::FuturesAsyncReadCompatExt;
 // futures::io::AsyncRead.
 use futures::stream::TryStreamExt;
 
+use futures::{future, stream, Stream, StreamExt};
+use std::pin::pin;
+
 use crate::{
     cellar,
     config::Config,
+    events::{self, Event, Sender},
     inventory,
-    types::{Installation, Package},
+    retry::retry,
+    types::{host, Installation, Package},
 };
 
-pub enum InstallEvent {
-    DownloadSize(u64), // Total size of the download in bytes
-    Progress(u64),     // we downloaded n bytes
-}
-
 //TODO set UserAgent
 
-pub async fn install<F>(
+pub async fn install(
     pkg: &Package,
     config: &Config,
-    mut event_callback: Option<F>,
-) -> Result<Installation, Box<dyn Error>>
-where
-    F: FnMut(InstallEvent) + Send + 'static,
-{
-    let shelf = config.pkgx_dir.join(&pkg.project);
-    fs::create_dir_all(&shelf)?;
+    events: Option<Sender>,
+) -> Result<Installation, Error> {
+    let path = config.pkgx_dir.join(&pkg.project);
+    fs::create_dir_all(&path)?;
     let shelf = OpenOptions::new()
         .read(true) // Open the directory in read-only mode
-        .open(shelf)?;
+        .open(&path)?;
 
+    debug!(%pkg, "waiting for shelf lock");
     task::spawn_blocking({
         let shelf = shelf.try_clone()?;
-        move || {
-            shelf
-                .lock_exclusive()
-                .expect("couldn’t obtain lock, is another pkgx instance running?");
-        }
+        move || shelf.lock_exclusive()
     })
-    .await?;
+    .await
+    .map_err(std::io::Error::from)?
+    .map_err(|source| Error::Lock { path, source })?;
+    debug!(%pkg, "acquired shelf lock");
 
+    let start = Instant::now();
     let url = inventory::get_url(pkg, config);
-    let client = Client::new();
-    let rsp = client.get(url).send().await?.error_for_status()?;
+    retry(config.retries, || {
+        download(pkg, &url, config, events.as_ref())
+    })
+    .await?;
 
-    let total_size = rsp
-        .content_length()
-        .ok_or("Failed to get content length from response")?;
+    let installation = Installation {
+        path: cellar::dst(pkg, config),
+        pkg: pkg.clone(),
+    };
+
+    symlink(&installation, config).await?;
 
-    if let Some(cb) = event_callback.as_mut() {
-        cb(InstallEvent::DownloadSize(total_size));
+    FileExt::unlock(&shelf)?;
+    debug!(%pkg, path = %installation.path.display(), elapsed = ?start.elapsed(), "installed");
+    events::send(events.as_ref(), Event::Installed { pkg: pkg.clone() });
+
+    Ok(installation)
+}
+
+// bigger bottles are downloaded to a file first so they can resume
+const STREAM_MAX: u64 = 16 * 1024 * 1024;
+
+// downloads the bottle at `url` and unpacks it into the pkgx dir
+async fn download(
+    pkg: &Package,
+    url: &str,
+    config: &Config,
+    events: Option<&Sender>,
+) -> Result<(), Error> {
+    let partial = partial_path(pkg, config);
+    let resuming = fs::metadata(&partial).is_ok_and(|metadata| metadata.len() > 0);
+    match fetch(pkg, url, &partial, config, events).await {
+        // what we had wasn’t the start of this bottle after all
+        Err(Error::Checksum { .. }) if resuming => {
+            debug!(%url, "resumed download is corrupt, starting over");
+            fetch(pkg, url, &partial, config, events).await
+        }
+        rv => rv,
     }
+}
 
-    let stream = rsp.bytes_stream();
+async fn fetch(
+    pkg: &Package,
+    url: &str,
+    partial: &Path,
+    config: &Config,
+    events: Option<&Sender>,
+) -> Result<(), Error> {
+    let validator = validator_path(partial);
+    let (rsp, have) = loop {
+        let have = fs::metadata(partial).map_or(0, |metadata| metadata.len());
+        let mut rq = Client::new().get(url);
+        if have > 0 {
+            debug!(%url, bytes = have, "resuming download");
+            rq = rq.header(RANGE, format!("bytes={}-", have));
+            // so we get it all again if the bottle changed since
+            if let Ok(validator) = fs::read_to_string(&validator) {
+                rq = rq.header(IF_RANGE, validator);
+            }
+        } else {
+            debug!(%url, "downloading");
+        }
+        let rsp = rq.send().await?;
+        // we got it all last time but didn’t unpack it
+        if have > 0 && rsp.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            events::send(events, Event::Extracting { pkg: pkg.clone() });
+            return unpack_file(url, partial, config).await;
+        }
+        let rsp = rsp.error_for_status()?;
+        // servers may ignore the range and send it all
+        if have == 0 || rsp.status() != StatusCode::PARTIAL_CONTENT {
+            break (rsp, 0);
+        }
+        if content_range_start(&rsp) == Some(have) {
+            break (rsp, have);
+        }
+        debug!(%url, "server resumed from elsewhere, starting over");
+        discard(partial)?;
+    };
 
-    //TODO we don’t want to add inspect_ok to the stream at all in --silent mode
-    //  ^^ but the borrow checker despises us with a venom I can barely articulate if we try
-    let stream = stream.inspect_ok(move |chunk| {
-        if let Some(cb) = event_callback.as_mut() {
-            cb(InstallEvent::Progress(chunk.len() as u64));
+    let size = rsp.content_length().map(|n| n + have);
+    trace!(%pkg, bytes = size, "download size");
+    let started = Event::DownloadStarted {
+        pkg: pkg.clone(),
+        size,
+    };
+    events::send(events, started);
+
+    let headers = rsp.headers().clone();
+    let body = body(pkg, rsp, events);
+    if have == 0 && size.is_some_and(|size| size <= STREAM_MAX) {
+        // eg. the server ignored our range
+        discard(partial)?;
+        return unpack_stream(body, config).await;
+    }
+
+    if have > 0 {
+        // the bytes from last time
+        let pkg = pkg.clone();
+        events::send(events, Event::Chunk { pkg, n: have });
+    } else {
+        fs::create_dir_all(partial.parent().unwrap())?;
+        // a strong ETag or else the Last-Modified date, as `If-Range` wants
+        let etag = headers
+            .get(ETAG)
+            .filter(|etag| !etag.as_bytes().starts_with(b"W/"));
+        match etag.or(headers.get(LAST_MODIFIED)) {
+            Some(value) => fs::write(&validator, value.as_bytes())?,
+            None => remove(&validator)?,
+        }
+    }
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(have > 0)
+        .truncate(have == 0)
+        .open(partial)
+        .await?;
+    let mut body = pin!(body);
+    while let Some(chunk) = body.try_next().await? {
+        file.write_all(chunk.as_ref()).await?;
+    }
+    file.flush().await?;
+    drop(file);
+
+    unpack_file(url, partial, config).await
+}
+
+// `bytes 1024-2047/2048` → `1024`
+fn content_range_start(rsp: &Response) -> Option<u64> {
+    let range = rsp.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    let (start, _) = range.strip_prefix("bytes ")?.split_once('-')?;
+    start.parse().ok()
+}
+
+// the partial download’s `If-Range` validator is kept beside it
+fn validator_path(partial: &Path) -> PathBuf {
+    let mut path = partial.as_os_str().to_owned();
+    path.push(".validator");
+    PathBuf::from(path)
+}
+
+// so the next download starts over
+fn discard(partial: &Path) -> std::io::Result<()> {
+    remove(partial)?;
+    remove(&validator_path(partial))
+}
+
+fn remove(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+// the response’s body, sending a `Chunk` for each chunk and, once it has all
+// arrived, `Extracting`
+fn body(
+    pkg: &Package,
+    rsp: Response,
+    events: Option<&Sender>,
+) -> impl Stream<Item = reqwest::Result<impl AsRef<[u8]>>> {
+    let chunks = rsp.bytes_stream().inspect_ok({
+        let events = events.cloned();
+        let pkg = pkg.clone();
+        move |chunk| {
+            let n = chunk.len() as u64;
+            let pkg = pkg.clone();
+            events::send(events.as_ref(), Event::Chunk { pkg, n });
         }
     });
+    let done = stream::once({
+        let events = events.cloned();
+        let pkg = pkg.clone();
+        async move { events::send(events.as_ref(), Event::Extracting { pkg }) }
+    })
+    .filter_map(|()| future::ready(None));
+    chunks.chain(done)
+}
 
-    let stream = stream
-        .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
-        .into_async_read();
-    let stream = stream.compat();
+// `~/.cache/pkgx/bottles/foo.com/linux/x86-64/v1.2.3.tar.xz.partial`
+pub(crate) fn partial_path(pkg: &Package, config: &Config) -> PathBuf {
+    let (platform, arch) = host();
+    let cache_dir = config.pantry_dir.parent().unwrap();
+    cache_dir.join(format!(
+        "bottles/{}/{}/{}/v{}.tar.xz.partial",
+        pkg.project, platform, arch, pkg.version.raw
+    ))
+}
 
-    // Step 2: Create a XZ decoder
-    let decoder = XzDecoder::new(stream);
+// unpacks the download once it matches its checksum
+async fn unpack_file(url: &str, path: &Path, config: &Config) -> Result<(), Error> {
+    if let Err(err) = verify(url, path).await {
+        if let Error::Checksum { .. } = err {
+            discard(path)?;
+        }
+        return Err(err);
+    }
 
-    // Step 3: Extract the tar archive
-    let mut archive = Archive::new(decoder);
-    archive.unpack(&config.pkgx_dir).await?;
+    let file = tokio::fs::File::open(path).await?;
+    unpack(BufReader::new(file), config).await?;
+    discard(path)?;
+    Ok(())
+}
 
-    let installation = Installation {
-        path: cellar::dst(pkg, config),
-        pkg: pkg.clone(),
-    };
+// against the `.sha256sum` next to the bottle
+async fn verify(url: &str, path: &Path) -> Result<(), Error> {
+    let rsp = Client::new()
+        .get(format!("{}.sha256sum", url))
+        .send()
+        .await?
+        .error_for_status()?;
+    // `sha256sum` output: `<checksum>  <filename>`
+    let expected = rsp.text().await?;
+    let expected = expected.split_whitespace().next().unwrap_or_default();
+
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        let n = file.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    let actual = format!("{:x}", hasher.finalize());
 
-    symlink(&installation, config).await?;
+    if !actual.eq_ignore_ascii_case(expected) {
+        debug!(%url, expected, actual, "checksum mismatch");
+        return Err(Error::Checksum {
+            url: url.to_string(),
+        });
+    }
+    Ok(())
+}
 
-    FileExt::unlock(&shelf)?;
+// unpacks as it downloads, for small bottles
+async fn unpack_stream(
+    body: impl Stream<Item = reqwest::Result<impl AsRef<[u8]>>>,
+    config: &Config,
+) -> Result<(), Error> {
+    // the unpacker sees body errors as io errors, keep the real one for `retry`
+    let failed = Arc::new(Mutex::new(None));
+    let body = pin!(body);
+    let stream = body
+        .map_err({
+            let failed = failed.clone();
+            move |err| {
+                let io = futures::io::Error::other(err.to_string());
+                *failed.lock().unwrap() = Some(Error::from(err));
+                io
+            }
+        })
+        .into_async_read();
+    let stream = stream.compat();
 
-    Ok(installation)
+    if let Err(err) = unpack(stream, config).await {
+        return Err(failed.lock().unwrap().take().unwrap_or(err.into()));
+    }
+    Ok(())
+}
+
+async fn unpack(bottle: impl AsyncBufRead + Unpin, config: &Config) -> std::io::Result<()> {
+    let decoder = XzDecoder::new(bottle);
+    let mut archive = Archive::new(decoder);
+    archive.unpack(&config.pkgx_dir).await?;
+    // read whatever follows the archive so streamed bodies reach their end
+    if let Ok(decoder) = archive.into_inner() {
+        tokio::io::copy(&mut decoder.into_inner(), &mut tokio::io::sink()).await?;
+    }
+    Ok(())
 }
 
 use libsemverator::range::Range as VersionReq;
@@ -103,7 +342,7 @@ use std::collections::VecDeque;
 use std::fs;
 use std::path::Path;
 
-async fn symlink(installation: &Installation, config: &Config) -> Result<(), Box<dyn Error>> {
+async fn symlink(installation: &Installation, config: &Config) -> Result<(), Error> {
     let mut versions: VecDeque<(Version, PathBuf)> = cellar::ls(&installation.pkg.project, config)
         .await?
         .into_iter()
@@ -113,7 +352,7 @@ async fn symlink(installation: &Installation, config: &Config) -> Result<(), Box
     versions.make_contiguous().sort_by(|a, b| a.0.cmp(&b.0));
 
     if versions.is_empty() {
-        return Err(format!("no versions for package {}", installation.pkg.project).into());
+        return Err(Error::NotFound(installation.pkg.to_string()));
     }
 
     let shelf = installation.path.parent().unwrap();
@@ -123,12 +362,11 @@ async fn symlink(installation: &Installation, config: &Config) -> Result<(), Box
         "{}.{}",
         installation.pkg.version.major, installation.pkg.version.minor
     );
-    let minor_range = VersionReq::parse(&format!("^{}", v_mm))?;
+    let minor_range = range(&v_mm)?;
     let most_minor = versions
         .iter()
-        .filter(|(version, _)| minor_range.satisfies(version))
-        .last()
-        .ok_or_else(|| anyhow::anyhow!("Could not find most minor version"))?;
+        .rfind(|(version, _)| minor_range.satisfies(version))
+        .ok_or_else(|| Error::NotFound(installation.pkg.to_string()))?;
 
     if most_minor.0 != installation.pkg.version {
         return Ok(());
@@ -137,13 +375,12 @@ async fn symlink(installation: &Installation, config: &Config) -> Result<(), Box
     make_symlink(shelf, &format!("v{}", v_mm), installation).await?;
 
     // bug in semverator
-    let major_range = VersionReq::parse(&format!("^{}", installation.pkg.version.major))?;
+    let major_range = range(&installation.pkg.version.major.to_string())?;
 
     let most_major = versions
         .iter()
-        .filter(|(version, _)| major_range.satisfies(version))
-        .last()
-        .ok_or_else(|| anyhow::anyhow!("Could not find most major version"))?;
+        .rfind(|(version, _)| major_range.satisfies(version))
+        .ok_or_else(|| Error::NotFound(installation.pkg.to_string()))?;
 
     if most_major.0 != installation.pkg.version {
         return Ok(());
@@ -163,12 +400,18 @@ async fn symlink(installation: &Installation, config: &Config) -> Result<(), Box
     Ok(())
 }
 
+// `^{version}`
+fn range(version: &str) -> Result<VersionReq, Error> {
+    VersionReq::parse(&format!("^{}", version)).map_err(|err| Error::Invalid(err.to_string()))
+}
+
 async fn make_symlink(
     shelf: &Path,
     symname: &str,
     installation: &Installation,
-) -> Result<(), Box<dyn Error>> {
+) -> Result<(), Error> {
     let symlink_path = shelf.join(symname);
+    trace!(path = %symlink_path.display(), "symlinking");
 
     if symlink_path.is_symlink() {
         if let Err(err) = fs::remove_file(&symlink_path) {
@@ -181,7 +424,7 @@ async fn make_symlink(
     let target = installation
         .path
         .file_name()
-        .ok_or_else(|| anyhow::anyhow!("Could not get the base name of the installation path"))?;
+        .ok_or_else(|| Error::NotFound(installation.path.display().to_string()))?;
 
     match std::os::unix::fs::symlink(target, &symlink_path) {
         Ok(_) => Ok(()),
@@ -1,44 +1,64 @@
-use std::error::Error;
-use std::sync::Arc;
-
-use crate::install::{install, InstallEvent};
+use crate::events::{self, Event, Sender};
+use crate::install::install;
 use crate::types::{Installation, Package};
-use futures::stream::FuturesUnordered;
+use crate::Error;
+use futures::stream;
 use futures::StreamExt;
+use std::future::Future;
 
 use crate::config::Config;
 
-pub trait ProgressBarExt {
-    fn inc(&self, n: u64);
-    fn inc_length(&self, n: u64);
-}
-
+/// Installs the `pending` pkgs, `config.jobs` at a time, reporting progress to
+/// `events`. One failing doesn’t stop the others, all the failures are returned
+/// together once they are done.
 pub async fn install_multi(
     pending: &[Package],
     config: &Config,
-    pb: Option<Arc<impl ProgressBarExt + Send + Sync + 'static>>,
-) -> Result<Vec<Installation>, Box<dyn Error>> {
-    pending
-        .iter()
+    events: Option<Sender>,
+) -> Result<Vec<Installation>, Error> {
+    install_each(pending, config, events, install).await
+}
+
+// `install` is passed in so tests can see how many run at once
+pub(crate) async fn install_each<'a, F, Fut>(
+    pending: &'a [Package],
+    config: &'a Config,
+    events: Option<Sender>,
+    install: F,
+) -> Result<Vec<Installation>, Error>
+where
+    F: Fn(&'a Package, &'a Config, Option<Sender>) -> Fut,
+    Fut: Future<Output = Result<Installation, Error>>,
+{
+    let results = stream::iter(pending)
         .map(|pkg| {
-            install(
-                pkg,
-                config,
-                pb.clone().map(|pb| {
-                    move |event| match event {
-                        InstallEvent::DownloadSize(size) => {
-                            pb.inc_length(size);
-                        }
-                        InstallEvent::Progress(chunk) => {
-                            pb.inc(chunk);
-                        }
-                    }
-                }),
-            )
+            let events = events.clone();
+            let installing = install(pkg, config, events.clone());
+            async move {
+                let rv = installing.await;
+                if let Err(err) = &rv {
+                    let pkg = pkg.clone();
+                    let err = err.to_string();
+                    events::send(events.as_ref(), Event::Failed { pkg, err });
+                }
+                (pkg, rv)
+            }
         })
-        .collect::<FuturesUnordered<_>>()
+        .buffer_unordered(config.jobs.max(1))
         .collect::<Vec<_>>()
-        .await
-        .into_iter()
-        .collect()
+        .await;
+
+    let mut installations = vec![];
+    let mut failures = vec![];
+    for (pkg, rv) in results {
+        match rv {
+            Ok(installation) => installations.push(installation),
+            Err(err) => failures.push((pkg.clone(), err)),
+        }
+    }
+    match failures.len() {
+        0 => Ok(installations),
+        1 => Err(failures.remove(0).1),
+        _ => Err(Error::Install(failures)),
+    }
 }
@@ -1,59 +1,65 @@
 use crate::config::Config;
+use crate::retry::retry;
 use crate::types::{host, Package, PackageReq};
+use crate::Error;
 use libsemverator::semver::Semver as Version;
 use reqwest::Url;
-use std::error::Error;
-
-// Custom error for download issues
-#[derive(Debug)]
-pub struct DownloadError {
-    pub status: u16,
-    pub src: String,
-}
-
-impl std::fmt::Display for DownloadError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Download error: status code {} from {}",
-            self.status, self.src
-        )
-    }
-}
-
-impl Error for DownloadError {}
+use std::time::Instant;
+use tracing::{debug, trace};
 
 // Select function to pick a version
-pub async fn select(rq: &PackageReq, config: &Config) -> Result<Option<Version>, Box<dyn Error>> {
+pub async fn select(rq: &PackageReq, config: &Config) -> Result<Option<Version>, Error> {
     let versions = ls(rq, config).await?;
 
-    Ok(versions
+    let rv = versions
         .iter()
         .filter(|v| rq.constraint.satisfies(v))
         .max()
-        .cloned())
+        .cloned();
+    match &rv {
+        Some(version) => debug!(pkg = %rq, %version, "selected version"),
+        None => debug!(pkg = %rq, "no version satisfies"),
+    }
+    Ok(rv)
 }
 
 // Get function to fetch available versions
-pub async fn ls(rq: &PackageReq, config: &Config) -> Result<Vec<Version>, Box<dyn Error>> {
+pub async fn ls(rq: &PackageReq, config: &Config) -> Result<Vec<Version>, Error> {
     let base_url = config.dist_url.clone();
 
     let (platform, arch) = host();
-    let url = Url::parse(&format!(
+    let url = format!(
         "{}/{}/{}/{}/versions.txt",
         base_url, rq.project, platform, arch
-    ))?;
+    );
+    let url = Url::parse(&url).map_err(|err| Error::Network {
+        url,
+        status: None,
+        reason: err.to_string(),
+        transient: false,
+    })?;
 
-    let rsp = reqwest::get(url.clone()).await?;
+    debug!(%url, "fetching versions");
+    let start = Instant::now();
+    let releases = retry(config.retries, || async {
+        let rsp = reqwest::get(url.clone()).await?;
 
-    if !rsp.status().is_success() {
-        return Err(Box::new(DownloadError {
-            status: rsp.status().as_u16(),
-            src: url.to_string(),
-        }));
-    }
+        if rsp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(Error::NotFound(rq.project.clone()));
+        }
+        if !rsp.status().is_success() {
+            return Err(Error::Network {
+                url: url.to_string(),
+                status: Some(rsp.status().as_u16()),
+                reason: rsp.status().to_string(),
+                transient: false,
+            });
+        }
+
+        Ok(rsp.text().await?)
+    })
+    .await?;
 
-    let releases = rsp.text().await?;
     let mut versions: Vec<Version> = releases
         .lines()
         .map(Version::parse)
@@ -61,18 +67,17 @@ pub async fn ls(rq: &PackageReq, config: &Config) -> Result<Vec<Version>, Box<dy
         .collect();
 
     if versions.is_empty() {
-        return Err(Box::new(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("No versions for {}", rq.project),
-        )));
+        return Err(Error::NotFound(format!("versions of {}", rq.project)));
     }
 
     if rq.project == "openssl.org" {
         // Workaround: Remove specific version
-        let excluded_version = Version::parse("1.1.118")?;
-        versions.retain(|x| x != &excluded_version);
+        if let Ok(excluded_version) = Version::parse("1.1.118") {
+            versions.retain(|x| x != &excluded_version);
+        }
     }
 
+    trace!(project = %rq.project, count = versions.len(), elapsed = ?start.elapsed(), "fetched versions");
     Ok(versions)
 }
 
@@ -1,13 +1,34 @@
-mod cellar;
+pub mod cellar;
 pub mod config;
+pub mod detect;
 pub mod env;
+pub mod env_cache;
+mod error;
+pub mod events;
+mod ffi;
 pub mod hydrate;
 mod install;
 pub mod install_multi;
 mod inventory;
+pub mod manifest;
+pub mod moustache;
 mod pantry;
 pub mod pantry_db;
+mod pkgx;
 pub mod resolve;
+mod retry;
+pub mod run;
+#[cfg(target_os = "linux")]
+pub mod sandbox;
+pub mod script;
+pub mod shell;
 pub mod sync;
+#[cfg(test)]
+mod tests;
 pub mod types;
+pub mod user_config;
 pub mod utils;
+pub mod which;
+
+pub use error::{Error, Result};
+pub use pkgx::Pkgx;
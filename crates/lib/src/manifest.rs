@@ -0,0 +1,134 @@
+use crate::detect::detect;
+use crate::pantry::Deps;
+use crate::types::PackageReq;
+use crate::Error;
+use libsemverator::range::Range as VersionReq;
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// in order of preference, the first with a `dependencies` key wins
+pub const FILENAMES: [&str; 4] = ["pkgx.yaml", "pkgx.yml", ".pkgx.yaml", ".pkgx.yml"];
+
+#[derive(Debug, Default)]
+pub struct Manifest {
+    /// the files that contributed deps, eg. `pkgx.yaml` and `.nvmrc`
+    pub paths: Vec<PathBuf>,
+    /// projects or programs, eg. `nodejs.org^20` or `node@20`
+    pub deps: Vec<PackageReq>,
+}
+
+impl Manifest {
+    /// Drops deps for a project an earlier dep already asks for, `project`
+    /// maps programs to their projects, eg. `node@20` to `nodejs.org`.
+    pub fn dedup<F>(&mut self, mut project: F)
+    where
+        F: FnMut(&PackageReq) -> Option<String>,
+    {
+        let mut seen = HashSet::new();
+        self.deps.retain(|dep| {
+            let project = project(dep).unwrap_or_else(|| dep.project.clone());
+            seen.insert(project)
+        });
+    }
+}
+
+/// Finds the nearest directory (`dir` or its parents) with a pkgx manifest or
+/// the version files of other ecosystems (see `detect`) and merges them.
+pub fn find(dir: &Path) -> Result<Option<Manifest>, Error> {
+    for dir in dir.ancestors() {
+        let mut manifest = load(dir)?.unwrap_or_default();
+
+        for (path, dep) in detect(dir)? {
+            // the pkgx manifest is more specific
+            if manifest.deps.iter().any(|x| x.project == dep.project) {
+                continue;
+            }
+            if !manifest.paths.contains(&path) {
+                manifest.paths.push(path);
+            }
+            manifest.deps.push(dep);
+        }
+
+        if !manifest.paths.is_empty() {
+            return Ok(Some(manifest));
+        }
+    }
+    Ok(None)
+}
+
+fn load(dir: &Path) -> Result<Option<Manifest>, Error> {
+    for filename in FILENAMES {
+        let path = dir.join(filename);
+        if !path.is_file() {
+            continue;
+        }
+        let content = fs::read_to_string(&path)?;
+        let deps = parse(&content)
+            .map_err(|e| Error::Invalid(format!("invalid manifest: {}: {}", path.display(), e)))?;
+        if let Some(deps) = deps {
+            let paths = vec![path];
+            return Ok(Some(Manifest { paths, deps }));
+        }
+    }
+    Ok(None)
+}
+
+#[derive(Deserialize)]
+struct RawManifest {
+    dependencies: Option<RawDeps>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawDeps {
+    String(String),
+    List(Vec<String>),
+    Map(Deps),
+}
+
+/// `None` if there is no `dependencies` key, which accepts any of:
+///
+/// ```yaml
+/// dependencies: node@20 python~3.11
+/// dependencies: [node@20, python~3.11]
+/// dependencies:
+///   nodejs.org: 20
+///   python.org: ~3.11
+/// ```
+pub(crate) fn parse(content: &str) -> Result<Option<Vec<PackageReq>>, Error> {
+    if content.trim().is_empty() {
+        return Ok(None);
+    }
+    let raw: RawManifest =
+        serde_yaml::from_str(content).map_err(|err| Error::Invalid(err.to_string()))?;
+    let deps = match raw.dependencies {
+        None => return Ok(None),
+        Some(RawDeps::String(pkgspecs)) => pkgspecs
+            .split_whitespace()
+            .map(PackageReq::parse)
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(RawDeps::List(pkgspecs)) => pkgspecs
+            .iter()
+            .map(|pkgspec| PackageReq::parse(pkgspec))
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(RawDeps::Map(deps)) => {
+            let mut deps = deps
+                .0
+                .iter()
+                .map(|(project, constraint)| {
+                    VersionReq::parse(constraint).map(|constraint| PackageReq {
+                        project: project.clone(),
+                        constraint,
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| Error::Invalid(err.to_string()))?;
+            // maps are unordered, be deterministic
+            deps.sort_by(|a, b| a.project.cmp(&b.project));
+            deps
+        }
+    };
+    Ok(Some(deps))
+}
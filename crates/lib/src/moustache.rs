@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq)]
+pub enum MoustacheError {
+    Unresolved(String),
+    Unterminated(String),
+}
+
+impl std::fmt::Display for MoustacheError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoustacheError::Unresolved(key) => write!(f, "couldn’t resolve {{{{{}}}}}", key),
+            MoustacheError::Unterminated(template) => {
+                write!(f, "unterminated moustache: {}", template)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MoustacheError {}
+
+/// Replaces each `{{key}}` in `template` with its value from `vars`.
+///
+/// Whitespace inside the braces is ignored and so is a `$` before them since
+/// the pantry sometimes uses the GitHub Actions form: `${{ prefix }}`. An
+/// escaped `$`, ie. `$$` or `\$`, stays as it is.
+pub fn render(template: &str, vars: &HashMap<String, String>) -> Result<String, MoustacheError> {
+    let mut rv = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let literal = &rest[..start];
+        let literal = match literal.strip_suffix('$') {
+            Some(before) if !before.ends_with(['$', '\\']) => before,
+            _ => literal,
+        };
+        rv.push_str(literal);
+
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| MoustacheError::Unterminated(template.to_string()))?;
+        let key = rest[start + 2..start + end].trim();
+        let value = vars
+            .get(key)
+            .ok_or_else(|| MoustacheError::Unresolved(key.to_string()))?;
+        rv.push_str(value);
+
+        rest = &rest[start + end + 2..];
+    }
+    rv.push_str(rest);
+
+    Ok(rv)
+}
@@ -1,3 +1,4 @@
+use crate::Error;
 use crate::{config::Config, types::PackageReq};
 use libsemverator::range::Range as VersionReq;
 use serde::Deserialize;
@@ -12,10 +13,20 @@ pub struct PantryEntry {
     pub programs: Vec<String>,
     pub companions: Vec<PackageReq>,
     pub env: HashMap<String, String>,
+    pub interprets: Option<Interprets>,
+    /// extra directories for environment variables, eg. `PYTHONPATH: lib/foo`
+    pub search_paths: HashMap<String, Vec<String>>,
+}
+
+/// scripts with these extensions are run via `args`, eg. `deno run`
+#[derive(Debug)]
+pub struct Interprets {
+    pub extensions: Vec<String>,
+    pub args: Vec<String>,
 }
 
 impl PantryEntry {
-    fn from_path(path: &PathBuf, pantry_dir: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
+    fn from_path(path: &PathBuf, pantry_dir: &PathBuf) -> Result<Self, Error> {
         let project = path
             .parent()
             .unwrap()
@@ -25,13 +36,12 @@ impl PantryEntry {
             .unwrap()
             .to_string();
 
-        Self::from_raw_entry(RawPantryEntry::from_path(path)?, project)
+        let content = fs::read_to_string(path)?;
+        let entry = serde_yaml::from_str(&content).map_err(|err| Error::pantry(&project, err))?;
+        Self::from_raw_entry(entry, project)
     }
 
-    fn from_raw_entry(
-        entry: RawPantryEntry,
-        project: String,
-    ) -> Result<Self, Box<dyn std::error::Error>> {
+    fn from_raw_entry(entry: RawPantryEntry, project: String) -> Result<Self, Error> {
         let deps = if let Some(deps) = entry.dependencies {
             deps.0
                 .iter()
@@ -41,7 +51,8 @@ impl PantryEntry {
                         constraint,
                     })
                 })
-                .collect::<Result<Vec<_>, _>>()?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| Error::pantry(&project, err))?
         } else {
             vec![]
         };
@@ -62,7 +73,8 @@ impl PantryEntry {
                         constraint,
                     })
                 })
-                .collect::<Result<Vec<_>, _>>()?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|err| Error::pantry(&project, err))?
         } else {
             vec![]
         };
@@ -73,12 +85,34 @@ impl PantryEntry {
             HashMap::new()
         };
 
+        let interprets = entry.interprets.and_then(|interprets| {
+            let extensions = interprets.extensions.0;
+            let args = interprets.args.0;
+            if extensions.is_empty() || args.is_empty() {
+                None
+            } else {
+                Some(Interprets { extensions, args })
+            }
+        });
+
+        let search_paths = entry
+            .search_paths
+            .map(|search_paths| {
+                search_paths
+                    .into_iter()
+                    .map(|(key, suffixes)| (key, suffixes.0))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Ok(Self {
             deps,
             project,
             env,
             companions,
             programs,
+            interprets,
+            search_paths,
         })
     }
 }
@@ -131,6 +165,39 @@ struct RawPantryEntry {
     provides: Option<Provides>,
     companions: Option<Deps>,
     runtime: Option<Runtime>,
+    interprets: Option<RawInterprets>,
+    #[serde(rename = "search-paths")]
+    search_paths: Option<HashMap<String, OneOrMany>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawInterprets {
+    extensions: OneOrMany,
+    args: OneOrMany,
+}
+
+#[derive(Debug)]
+struct OneOrMany(Vec<String>);
+
+impl<'de> Deserialize<'de> for OneOrMany {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum OneOrManyHelper {
+            One(String),
+            Many(Vec<String>),
+        }
+
+        match OneOrManyHelper::deserialize(deserializer)? {
+            OneOrManyHelper::One(one) => Ok(OneOrMany(
+                one.split_whitespace().map(|x| x.to_string()).collect(),
+            )),
+            OneOrManyHelper::Many(many) => Ok(OneOrMany(many)),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -202,7 +269,7 @@ impl<'de> Deserialize<'de> for Runtime {
 }
 
 #[derive(Debug)]
-struct Deps(HashMap<String, String>);
+pub(crate) struct Deps(pub HashMap<String, String>);
 
 impl<'de> Deserialize<'de> for Deps {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -292,10 +359,3 @@ impl<'de> Deserialize<'de> for Provides {
         }
     }
 }
-
-impl RawPantryEntry {
-    fn from_path(path: &PathBuf) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        Ok(serde_yaml::from_str(&content)?)
-    }
-}
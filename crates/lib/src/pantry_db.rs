@@ -1,10 +1,17 @@
-use std::{collections::HashMap, error::Error};
+use crate::Error;
+use std::{collections::HashMap, path::Path};
 
+use lazy_static::lazy_static;
+use libsemverator::range::Range as VersionReq;
+use regex::Regex;
 use rusqlite::{params, Connection};
 
 use crate::{config::Config, pantry, types::PackageReq};
 
-pub fn cache(config: &Config, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+// bump whenever the tables below change so existing caches get rebuilt
+const SCHEMA_VERSION: i64 = 3;
+
+pub fn cache(config: &Config, conn: &mut Connection) -> Result<(), Error> {
     conn.execute_batch(
         "
     PRAGMA synchronous = OFF;
@@ -14,9 +21,12 @@ pub fn cache(config: &Config, conn: &mut Connection) -> Result<(), Box<dyn Error
     DROP TABLE IF EXISTS dependencies;
     DROP TABLE IF EXISTS companions;
     DROP TABLE IF EXISTS runtime_env;
+    DROP TABLE IF EXISTS interprets;
+    DROP TABLE IF EXISTS search_paths;
     CREATE TABLE provides (
         project TEXT,
-        program TEXT
+        program TEXT,
+        path TEXT
     );
     CREATE TABLE dependencies (
         project TEXT,
@@ -30,26 +40,38 @@ pub fn cache(config: &Config, conn: &mut Connection) -> Result<(), Box<dyn Error
         project TEXT,
         envline TEXT
     );
+    CREATE TABLE interprets (
+        project TEXT,
+        extension TEXT,
+        args TEXT
+    );
+    CREATE TABLE search_paths (
+        project TEXT,
+        key TEXT,
+        suffix TEXT
+    );
     CREATE INDEX idx_project ON provides(project);
     CREATE INDEX idx_program ON provides(program);
     CREATE INDEX idx_project_dependencies ON dependencies(project);
     CREATE INDEX idx_project_companions ON companions(project);
+    CREATE INDEX idx_extension ON interprets(extension);
+    CREATE INDEX idx_project_search_paths ON search_paths(project);
     ",
     )?;
 
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+
     let tx = conn.transaction()?;
 
     for pkg in pantry::ls(config) {
-        for mut program in pkg.programs {
-            program = std::path::Path::new(&program)
-                .file_name()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string();
+        for path in pkg.programs {
+            let program = match Path::new(&path).file_name() {
+                Some(program) => program.to_string_lossy().to_string(),
+                None => continue,
+            };
             tx.execute(
-                "INSERT INTO provides (project, program) VALUES (?1, ?2);",
-                params![pkg.project, program],
+                "INSERT INTO provides (project, program, path) VALUES (?1, ?2, ?3);",
+                params![pkg.project, program, path],
             )?;
         }
 
@@ -73,6 +95,26 @@ pub fn cache(config: &Config, conn: &mut Connection) -> Result<(), Box<dyn Error
                 params![pkg.project, format!("{}={}", key, value)],
             )?;
         }
+
+        if let Some(interprets) = pkg.interprets {
+            // args are newline separated since they may (rarely) contain spaces
+            let args = interprets.args.join("\n");
+            for extension in interprets.extensions {
+                tx.execute(
+                    "INSERT INTO interprets (project, extension, args) VALUES (?1, ?2, ?3);",
+                    params![pkg.project, extension.trim_start_matches('.'), args],
+                )?;
+            }
+        }
+
+        for (key, suffixes) in pkg.search_paths {
+            for suffix in suffixes {
+                tx.execute(
+                    "INSERT INTO search_paths (project, key, suffix) VALUES (?1, ?2, ?3);",
+                    params![pkg.project, key, suffix],
+                )?;
+            }
+        }
     }
 
     tx.commit()?;
@@ -80,10 +122,7 @@ pub fn cache(config: &Config, conn: &mut Connection) -> Result<(), Box<dyn Error
     Ok(())
 }
 
-pub fn deps_for_project(
-    project: &String,
-    conn: &Connection,
-) -> Result<Vec<PackageReq>, Box<dyn Error>> {
+pub fn deps_for_project(project: &String, conn: &Connection) -> Result<Vec<PackageReq>, Error> {
     let mut stmt = conn.prepare("SELECT pkgspec FROM dependencies WHERE project = ?1")?;
     let rv = stmt.query_map(params![project], |row| {
         let pkgspec: String = row.get(0)?;
@@ -93,20 +132,152 @@ pub fn deps_for_project(
     Ok(rv.collect::<Result<Vec<_>, _>>()?)
 }
 
-pub fn which(cmd: &String, conn: &Connection) -> Result<Vec<String>, rusqlite::Error> {
-    let mut stmt = conn.prepare("SELECT project FROM provides WHERE program = ?1")?;
+/// eg. `["bin/node", "bin/npm"]`
+pub fn programs_for_project(
+    project: &str,
+    conn: &Connection,
+) -> Result<Vec<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT path FROM provides WHERE project = ?1 ORDER BY path")?;
+    let rv = stmt.query_map(params![project], |row| row.get(0))?;
+    rv.collect()
+}
+
+/// false if the cache was created by a pkgx with different tables
+pub fn is_current(conn: &Connection) -> bool {
+    conn.pragma_query_value(None, "user_version", |row| row.get::<_, i64>(0))
+        .map(|version| version == SCHEMA_VERSION)
+        .unwrap_or(false)
+}
+
+#[derive(Debug, Clone)]
+pub struct Provider {
+    pub project: String,
+    /// relative to the installation prefix, eg. `bin/python3.11`
+    pub path: String,
+    /// implied by versioned program names, eg. `python3.11` → `~3.11`
+    pub constraint: VersionReq,
+}
+
+/// Projects that provide `cmd`. Exact matches come first, then matches against
+/// versioned `provides` entries like `bin/python{{version.marketing}}`.
+pub fn which(cmd: &String, conn: &Connection) -> Result<Vec<Provider>, rusqlite::Error> {
+    let mut stmt =
+        conn.prepare("SELECT project, path FROM provides WHERE program = ?1 ORDER BY path")?;
     let mut rv = Vec::new();
     let mut rows = stmt.query(params![cmd])?;
     while let Some(row) = rows.next()? {
-        rv.push(row.get(0)?);
+        rv.push(Provider {
+            project: row.get(0)?,
+            path: row.get(1)?,
+            constraint: VersionReq::parse("*").unwrap(),
+        });
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT project, program, path FROM provides WHERE program LIKE '%{{%' ORDER BY path",
+    )?;
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let program: String = row.get(1)?;
+        if let Some(constraint) = match_versioned(&program, cmd) {
+            let path: String = row.get(2)?;
+            let path = match Path::new(&path).parent() {
+                Some(dir) => dir.join(cmd).to_string_lossy().to_string(),
+                None => cmd.clone(),
+            };
+            rv.push(Provider {
+                project: row.get(0)?,
+                path,
+                constraint,
+            });
+        }
+    }
+
+    Ok(rv)
+}
+
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    pub project: String,
+    /// eg. `["deno", "run"]`, the script path follows these
+    pub args: Vec<String>,
+}
+
+/// Projects that can run scripts with this extension (given without the `.`)
+pub fn interpreters(
+    extension: &str,
+    conn: &Connection,
+) -> Result<Vec<Interpreter>, rusqlite::Error> {
+    let mut stmt =
+        conn.prepare("SELECT project, args FROM interprets WHERE extension = ?1 ORDER BY project")?;
+    let mut rv = Vec::new();
+    let mut rows = stmt.query(params![extension])?;
+    while let Some(row) = rows.next()? {
+        let args: String = row.get(1)?;
+        rv.push(Interpreter {
+            project: row.get(0)?,
+            args: args.split('\n').map(|arg| arg.to_string()).collect(),
+        });
     }
     Ok(rv)
 }
 
+lazy_static! {
+    static ref MOUSTACHE_REGEX: Regex = Regex::new(r"\{\{\s*version(\.\w+)?\s*\}\}").unwrap();
+}
+
+/// Matches `cmd` against a `provides` template, eg. `python{{version.marketing}}`
+/// matches `python3.11` and yields the constraint `@3.11`.
+pub(crate) fn match_versioned(template: &str, cmd: &str) -> Option<VersionReq> {
+    let mut pattern = String::from("^");
+    let mut components = vec![];
+    let mut last = 0;
+    for caps in MOUSTACHE_REGEX.captures_iter(template) {
+        let whole = caps.get(0).unwrap();
+        pattern.push_str(&regex::escape(&template[last..whole.start()]));
+        last = whole.end();
+        let (capture, indices): (&str, &[usize]) = match caps.get(1).map(|m| m.as_str()) {
+            None => (r"(\d+(?:\.\d+)*)", &[0, 1, 2]),
+            Some(".major") => (r"(\d+)", &[0]),
+            Some(".minor") => (r"(\d+)", &[1]),
+            Some(".patch") => (r"(\d+)", &[2]),
+            Some(".marketing") => (r"(\d+\.\d+)", &[0, 1]),
+            Some(_) => return None,
+        };
+        pattern.push_str(capture);
+        components.push(indices);
+    }
+    if components.is_empty() {
+        return None;
+    }
+    pattern.push_str(&regex::escape(&template[last..]));
+    pattern.push('$');
+
+    let captures = Regex::new(&pattern).ok()?.captures(cmd)?;
+
+    let mut version: Vec<Option<&str>> = vec![None; 3];
+    for (i, indices) in components.iter().enumerate() {
+        let value = captures.get(i + 1)?.as_str();
+        for (index, part) in indices.iter().zip(value.split('.')) {
+            match version[*index] {
+                Some(existing) if existing != part => return None,
+                _ => version[*index] = Some(part),
+            }
+        }
+    }
+
+    // only a leading run of components makes sense as a constraint
+    let version: Vec<&str> = version.into_iter().map_while(|part| part).collect();
+    if version.is_empty() {
+        return None;
+    }
+    VersionReq::parse(&format!("@{}", version.join("."))).ok()
+}
+
 pub fn runtime_env_for_project(
     project: &String,
     conn: &Connection,
-) -> Result<HashMap<String, String>, Box<dyn Error>> {
+) -> Result<HashMap<String, String>, Error> {
     let sql = "SELECT envline FROM runtime_env WHERE project = ?1";
     let mut stmt = conn.prepare(sql)?;
     let mut rows = stmt.query(params![project])?;
@@ -119,10 +290,46 @@ pub fn runtime_env_for_project(
     Ok(env)
 }
 
+#[derive(Debug, Clone)]
+pub struct SearchPath {
+    pub project: String,
+    /// eg. `PYTHONPATH`
+    pub key: String,
+    /// relative to the installation, eg. `lib/python*/site-packages`
+    pub suffix: String,
+}
+
+/// The extra directories `projects` declare for environment variables
+pub fn search_paths_for_projects(
+    projects: &[String],
+    conn: &Connection,
+) -> Result<Vec<SearchPath>, Error> {
+    if projects.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let placeholders = projects.iter().map(|_| "?").collect::<Vec<_>>().join(", ");
+    let query = format!(
+        "SELECT project, key, suffix FROM search_paths WHERE project IN ({}) ORDER BY rowid",
+        placeholders
+    );
+
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(projects.iter()), |row| {
+        Ok(SearchPath {
+            project: row.get(0)?,
+            key: row.get(1)?,
+            suffix: row.get(2)?,
+        })
+    })?;
+
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
 pub fn companions_for_projects(
     projects: &[String],
     conn: &Connection,
-) -> Result<Vec<PackageReq>, Box<dyn Error>> {
+) -> Result<Vec<PackageReq>, Error> {
     if projects.is_empty() {
         return Ok(Vec::new());
     }
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+
+use rusqlite::Connection;
+use tracing::debug;
+
+use crate::{
+    cellar,
+    config::Config,
+    env,
+    events::{self, Event, Receiver, Sender},
+    hydrate::hydrate,
+    install_multi::install_multi,
+    pantry_db::{self, Provider},
+    resolve::{resolve, Resolution},
+    run::{self, Output},
+    sync,
+    types::{Installation, PackageReq},
+    user_config::UserConfig,
+    utils, which, Error,
+};
+
+/// What the `pkgx` command does, for embedding: the pkg-db connection and the
+/// steps between a program name and running it.
+///
+/// ```no_run
+/// # async fn example() -> Result<(), libpkgx::Error> {
+/// let mut pkgx = libpkgx::Pkgx::new(libpkgx::config::Config::new()?)?;
+/// let output = pkgx.run("node@20", &["--version".to_string()], true).await?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// The `pkgx` binary doesn’t use this: it prompts to disambiguate, syncs when
+/// a program isn’t found and runs shebangs and dev environments, none of which
+/// this has hooks for yet.
+pub struct Pkgx {
+    pub config: Config,
+    pub user_config: UserConfig,
+    conn: Connection,
+    events: Option<Sender>,
+}
+
+impl Pkgx {
+    /// Opens the pkg-db, which is synced by `run` or `sync` if needed.
+    pub fn new(config: Config) -> Result<Self, Error> {
+        let user_config = UserConfig::load(&config)?;
+        let cache_dir = config.pantry_dir.parent().unwrap();
+        std::fs::create_dir_all(cache_dir)?;
+        let conn = Connection::open(cache_dir.join("pantry.db"))?;
+        Ok(Self {
+            config,
+            user_config,
+            conn,
+            events: None,
+        })
+    }
+
+    /// Progress from now on is sent to the returned receiver.
+    pub fn events(&mut self) -> Receiver {
+        let (tx, rx) = events::channel();
+        self.events = Some(tx);
+        rx
+    }
+
+    /// For the lower level functions, eg. `pantry_db::deps_for_project`.
+    pub fn conn(&self) -> &Connection {
+        &self.conn
+    }
+
+    /// Replaces the pantry and the pkg-db with the latest.
+    pub async fn sync(&mut self) -> Result<(), Error> {
+        events::send(self.events.as_ref(), Event::Syncing);
+        sync::replace(&self.config, &mut self.conn).await
+    }
+
+    /// `sync`s if the pkg-db is missing, one from another version of pkgx is
+    /// rebuilt from the pantry we have.
+    pub async fn sync_if_needed(&mut self) -> Result<bool, Error> {
+        sync::upgrade(&self.config, &mut self.conn)?;
+        if sync::should(&self.config, &self.conn) {
+            self.sync().await?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// The project that provides `cmd`, ambiguities resolve per `which::rank`
+    /// unless the user’s `config.yml` has a preference.
+    pub fn which(&self, cmd: &str) -> Result<Provider, Error> {
+        let provider = which::which(cmd, &[], &self.conn, &self.user_config, |_, ranked| {
+            ranked.first().cloned()
+        })?;
+        Ok(provider)
+    }
+
+    /// `pkgspec` may name a program rather than a project, eg. `node@20` or
+    /// `python3.11`, which also constrains the version.
+    pub fn pkgreq(&self, pkgspec: &str) -> Result<PackageReq, Error> {
+        let rq = PackageReq::parse(pkgspec)?;
+        let projects = self.config.pantry_dir.join("projects");
+        if projects.join(&rq.project).join("package.yml").is_file() {
+            return Ok(rq);
+        }
+        let provider = self.which(&rq.project)?;
+        let constraint = rq.constraint.intersect(&provider.constraint).map_err(|_| {
+            Error::Conflict(format!(
+                "{} isn’t provided by {}",
+                pkgspec, provider.project
+            ))
+        })?;
+        Ok(PackageReq {
+            project: provider.project,
+            constraint,
+        })
+    }
+
+    /// The versions of `reqs`, their companions and their deps to use, and
+    /// which of those still need to be installed.
+    pub async fn resolve(&self, reqs: &[PackageReq]) -> Result<Resolution, Error> {
+        events::send(self.events.as_ref(), Event::Resolving);
+        let mut pkgs = reqs.to_vec();
+        let projects: Vec<String> = reqs.iter().map(|rq| rq.project.clone()).collect();
+        pkgs.extend(pantry_db::companions_for_projects(&projects, &self.conn)?);
+
+        let graph = hydrate(&pkgs, |project| {
+            pantry_db::deps_for_project(&project, &self.conn)
+        })
+        .await?;
+
+        let resolution = resolve(graph, &self.config).await?;
+        for installation in &resolution.installed {
+            let pkg = installation.pkg.clone();
+            events::send(self.events.as_ref(), Event::Cached { pkg });
+        }
+        Ok(resolution)
+    }
+
+    /// Installs what the `resolution` is pending and returns every installation.
+    pub async fn install(&self, resolution: &Resolution) -> Result<Vec<Installation>, Error> {
+        let mut rv = resolution.installed.clone();
+        if !resolution.pending.is_empty() {
+            let installed =
+                install_multi(&resolution.pending, &self.config, self.events.clone()).await?;
+            rv.extend(installed);
+        }
+        Ok(rv)
+    }
+
+    /// The current environment with the `installations` added, as `pkgx cmd` runs `cmd`.
+    pub fn env(&self, installations: &[Installation]) -> Result<HashMap<String, String>, Error> {
+        let rules = env::rules(installations, &self.conn, &self.user_config)?;
+        let mut env = env::mix(env::map(installations, &rules));
+        env.extend(env::values(installations, &rules));
+        env::mix_runtime(&env, &env::inherited(false), installations, &self.conn)
+    }
+
+    /// Installs whatever provides `cmd` (eg. `node@20`) and runs it, waiting
+    /// for it to finish, see `run::run`. A project (eg. `deno.land`) runs the
+    /// one program it provides.
+    pub async fn run(
+        &mut self,
+        cmd: &str,
+        args: &[String],
+        capture: bool,
+    ) -> Result<Output, Error> {
+        self.sync_if_needed().await?;
+
+        let rq = self.pkgreq(cmd)?;
+        let resolution = self.resolve(&[rq]).await?;
+        let installations = self.install(&resolution).await?;
+        let env = self.env(&installations)?;
+
+        // eg. `node` for `node@20`
+        let name = PackageReq::parse(cmd)?.project;
+        let provided = match self.which(&name) {
+            Ok(provider) => utils::provided_program(&provider, &installations),
+            // a project, eg. `deno.land`, runs the one program it provides
+            Err(_) => match pantry_db::programs_for_project(&name, &self.conn)?.as_slice() {
+                [path] => installations
+                    .iter()
+                    .find(|installation| installation.pkg.project == name)
+                    .map(|installation| installation.path.join(path).to_string_lossy().to_string()),
+                [] => None,
+                programs => {
+                    return Err(Error::Conflict(format!(
+                        "{} provides {}, pick one",
+                        name,
+                        programs.join(", ")
+                    )))
+                }
+            },
+        };
+        let program = match provided {
+            Some(program) => program,
+            None => {
+                let paths: Vec<String> = env
+                    .get("PATH")
+                    .map(|paths| paths.split(':').map(|x| x.to_string()).collect())
+                    .unwrap_or_default();
+                utils::find_program(&name, &paths).await?
+            }
+        };
+        debug!(program, ?args, "running");
+        Ok(run::run(&program, args, &env, capture)?)
+    }
+
+    /// Every installed pkg.
+    pub async fn installed(&self) -> Result<Vec<Installation>, Error> {
+        cellar::installed(&self.config).await
+    }
+}
@@ -1,7 +1,9 @@
 use crate::config::Config;
 use crate::types::{Installation, Package, PackageReq};
+use crate::Error;
 use crate::{cellar, inventory};
-use std::error::Error;
+use std::time::Instant;
+use tracing::debug;
 
 #[derive(Debug, Default)]
 pub struct Resolution {
@@ -18,8 +20,9 @@ pub struct Resolution {
 //TODO no need to take array since it doesn’t consider anything
 use futures::stream::{FuturesUnordered, StreamExt};
 
-pub async fn resolve(reqs: Vec<PackageReq>, config: &Config) -> Result<Resolution, Box<dyn Error>> {
+pub async fn resolve(reqs: Vec<PackageReq>, config: &Config) -> Result<Resolution, Error> {
     let mut rv = Resolution::default();
+    let start = Instant::now();
 
     // Create a FuturesUnordered to run the tasks concurrently
     let mut futures = FuturesUnordered::new();
@@ -27,18 +30,20 @@ pub async fn resolve(reqs: Vec<PackageReq>, config: &Config) -> Result<Resolutio
     for req in reqs {
         futures.push(async move {
             if let Some(installation) = cellar::has(&req, config).await {
-                Ok::<_, Box<dyn Error>>((
-                    Some((installation.clone(), installation.pkg.clone())),
-                    None,
-                ))
-            } else if let Ok(Some(version)) = inventory::select(&req, config).await {
-                let pkg = Package {
-                    project: req.project.clone(),
-                    version,
-                };
-                Ok::<_, Box<dyn Error>>((None, Some(pkg)))
+                debug!(pkg = %req, path = %installation.path.display(), "already installed");
+                Ok::<_, Error>((Some((installation.clone(), installation.pkg.clone())), None))
             } else {
-                Err(Box::new(ResolveError { pkg: req }) as Box<dyn Error>)
+                match inventory::select(&req, config).await? {
+                    Some(version) => {
+                        let pkg = Package {
+                            project: req.project.clone(),
+                            version,
+                        };
+                        debug!(%pkg, "pending install");
+                        Ok((None, Some(pkg)))
+                    }
+                    None => Err(Error::NotFound(req.to_string())),
+                }
             }
         });
     }
@@ -58,19 +63,11 @@ pub async fn resolve(reqs: Vec<PackageReq>, config: &Config) -> Result<Resolutio
         }
     }
 
+    debug!(
+        installed = rv.installed.len(),
+        pending = rv.pending.len(),
+        elapsed = ?start.elapsed(),
+        "resolved"
+    );
     Ok(rv)
 }
-
-use std::fmt;
-
-#[derive(Debug)]
-pub struct ResolveError {
-    pub pkg: PackageReq, // Holds the package or requirement
-}
-impl Error for ResolveError {}
-
-impl fmt::Display for ResolveError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "not-found: pkg: {:?}", self.pkg)
-    }
-}
@@ -0,0 +1,27 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::Error;
+
+/// Runs `f` again while it fails transiently, up to `retries` more times,
+/// waiting twice as long before each attempt.
+pub(crate) async fn retry<T, F, Fut>(retries: u32, mut f: F) -> Result<T, Error>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Error>>,
+{
+    let mut delay = Duration::from_millis(500);
+    for _ in 0..retries {
+        match f().await {
+            Err(err) if err.is_transient() => {
+                debug!(%err, ?delay, "retrying");
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            rv => return rv,
+        }
+    }
+    f().await
+}
@@ -0,0 +1,180 @@
+use nix::libc;
+use nix::sys::signal::{self, SaFlags, SigAction, SigHandler, SigSet, Signal};
+use std::collections::HashMap;
+use std::io::{self, Read};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::thread;
+
+/// forwarded to the child while we wait for it
+const FORWARDED: [Signal; 3] = [Signal::SIGINT, Signal::SIGTERM, Signal::SIGWINCH];
+
+// the pid signals are forwarded to, zero if nothing is running
+static CHILD: AtomicI32 = AtomicI32::new(0);
+
+// a signal that arrived before we knew the child’s pid, zero if none
+static PENDING: AtomicI32 = AtomicI32::new(0);
+
+#[derive(Debug)]
+pub struct Output {
+    /// the exit code or, if the child was killed by a signal, 128 + its number
+    pub status: i32,
+    /// `None` unless captured
+    pub stdout: Option<Vec<u8>>,
+    pub stderr: Option<Vec<u8>>,
+}
+
+/// Runs `cmd` with exactly `env` and waits for it, unlike `execve` pkgx is
+/// still around afterwards.
+///
+/// SIGINT, SIGTERM and SIGWINCH sent to us are forwarded to the child, except
+/// those from the terminal, which already reach it since it is in our process
+/// group.
+/// Only one command should be run at a time.
+pub fn run(
+    cmd: &str,
+    args: &[String],
+    env: &HashMap<String, String>,
+    capture: bool,
+) -> io::Result<Output> {
+    let mut command = Command::new(cmd);
+    command.args(args).env_clear().envs(env);
+    run_command(command, capture)
+}
+
+/// Like `run` for a `Command` that is already set up, eg. by `sandbox`.
+pub fn run_command(mut command: Command, capture: bool) -> io::Result<Output> {
+    if capture {
+        command.stdout(Stdio::piped()).stderr(Stdio::piped());
+    }
+
+    let previous = forward_signals()?;
+    let rv = command.spawn().and_then(wait);
+    CHILD.store(0, Ordering::SeqCst);
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    restore_signals(previous)?;
+    // there was no child to get it so it’s ours after all
+    if pending > 0 {
+        unsafe { libc::raise(pending) };
+    }
+
+    let (exit, stdout, stderr) = rv?;
+    Ok(Output {
+        status: status(exit),
+        stdout: capture.then_some(stdout),
+        stderr: capture.then_some(stderr),
+    })
+}
+
+// forwards signals to `child` until it exits, it is only reaped once `CHILD`
+// is cleared so a recycled pid can’t be signalled
+fn wait(mut child: Child) -> io::Result<(ExitStatus, Vec<u8>, Vec<u8>)> {
+    let pid = child.id() as i32;
+    CHILD.store(pid, Ordering::SeqCst);
+    let pending = PENDING.swap(0, Ordering::SeqCst);
+    if pending > 0 {
+        unsafe { libc::kill(pid, pending) };
+    }
+
+    // read while waiting so the child can’t block on a full pipe
+    let stdout = child.stdout.take().map(read);
+    let stderr = child.stderr.take().map(read);
+
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    let flags = libc::WEXITED | libc::WNOWAIT;
+    while unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } != 0 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            CHILD.store(0, Ordering::SeqCst);
+            return Err(err);
+        }
+    }
+    CHILD.store(0, Ordering::SeqCst);
+    let status = child.wait()?;
+
+    let join = |reader: Option<thread::JoinHandle<io::Result<Vec<u8>>>>| match reader {
+        Some(reader) => reader
+            .join()
+            .map_err(|_| io::Error::other("reader panicked"))?,
+        None => Ok(vec![]),
+    };
+    Ok((status, join(stdout)?, join(stderr)?))
+}
+
+fn read(mut pipe: impl Read + Send + 'static) -> thread::JoinHandle<io::Result<Vec<u8>>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        pipe.read_to_end(&mut buf)?;
+        Ok(buf)
+    })
+}
+
+/// The exit code a shell would report, eg. 130 for death by SIGINT.
+pub fn status(status: ExitStatus) -> i32 {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1,
+    }
+}
+
+extern "C" fn forward(signal: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+    let code = if info.is_null() {
+        0
+    } else {
+        unsafe { (*info).si_code }
+    };
+    if from_terminal(signal, code) {
+        return;
+    }
+    // whichever of us and `wait` takes it delivers it, once the pid is known
+    PENDING.store(signal, Ordering::SeqCst);
+    let pid = CHILD.load(Ordering::SeqCst);
+    if pid > 0 {
+        let signal = PENDING.swap(0, Ordering::SeqCst);
+        if signal > 0 {
+            unsafe { libc::kill(pid, signal) };
+        }
+    }
+}
+
+// not in `libc` for linux
+#[cfg(target_os = "linux")]
+const SI_KERNEL: libc::c_int = 0x80;
+
+/// True if the terminal sent `signal`, in which case it already went to the
+/// whole process group, the child included.
+#[cfg(target_os = "linux")]
+pub(crate) fn from_terminal(_signal: libc::c_int, si_code: libc::c_int) -> bool {
+    si_code == SI_KERNEL
+}
+
+/// Elsewhere the terminal’s signals can’t be told apart by `si_code`, so only
+/// SIGTERM, which terminals never send, is forwarded.
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn from_terminal(signal: libc::c_int, _si_code: libc::c_int) -> bool {
+    signal != libc::SIGTERM
+}
+
+fn forward_signals() -> io::Result<Vec<(Signal, SigAction)>> {
+    let action = SigAction::new(
+        SigHandler::SigAction(forward),
+        SaFlags::SA_RESTART | SaFlags::SA_SIGINFO,
+        SigSet::empty(),
+    );
+    FORWARDED
+        .iter()
+        .map(|&sig| {
+            let previous = unsafe { signal::sigaction(sig, &action) }?;
+            Ok((sig, previous))
+        })
+        .collect()
+}
+
+fn restore_signals(previous: Vec<(Signal, SigAction)>) -> io::Result<()> {
+    for (sig, action) in previous {
+        unsafe { signal::sigaction(sig, &action) }?;
+    }
+    Ok(())
+}
@@ -0,0 +1,333 @@
+use crate::{config::Config, types::Installation};
+use nix::libc;
+use nix::sched::{unshare, CloneFlags};
+use std::collections::{HashMap, HashSet};
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::{ffi::OsStrExt, process::CommandExt};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// the system directories sandboxed commands can read
+const SYSTEM: [&str; 7] = ["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/etc"];
+
+/// Runs commands in new user and mount (and optionally network) namespaces
+/// where only the paths we list exist.
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    /// visible but read-only, eg. the installations
+    pub read_only: Vec<PathBuf>,
+    /// visible and writable, eg. the current directory
+    pub read_write: Vec<PathBuf>,
+    pub network: bool,
+    // an empty directory we build the new root on
+    root: PathBuf,
+}
+
+impl Sandbox {
+    /// Sees the system directories, the pkgx dir and `installations` read-only
+    /// and the current directory read-write, unless that is `/`.
+    pub fn new(installations: &[Installation], config: &Config) -> io::Result<Self> {
+        let root = config.pantry_dir.parent().unwrap().join("sandbox");
+        fs::create_dir_all(&root)?;
+
+        let mut read_only: Vec<PathBuf> = SYSTEM.iter().map(PathBuf::from).collect();
+        // pkgs find each other through its `v*` symlinks
+        read_only.push(config.pkgx_dir.clone());
+        read_only.extend(installations.iter().map(|i| i.path.clone()));
+
+        let cwd = std::env::current_dir()?;
+        Ok(Sandbox {
+            read_only,
+            // that would be everything
+            read_write: vec![cwd]
+                .into_iter()
+                .filter(|cwd| *cwd != Path::new("/"))
+                .collect(),
+            network: true,
+            root,
+        })
+    }
+
+    /// `cmd` set up to run inside the sandbox, eg. via `run::run_command`.
+    pub fn command(
+        &self,
+        cmd: &str,
+        args: &[String],
+        env: &HashMap<String, String>,
+    ) -> io::Result<Command> {
+        let plan = self.plan()?;
+        let mut command = Command::new(cmd);
+        command.args(args).env_clear().envs(env);
+        // SAFETY: `enter` only makes syscalls, everything is allocated up front
+        unsafe { command.pre_exec(move || plan.enter()) };
+        Ok(command)
+    }
+
+    pub(crate) fn plan(&self) -> io::Result<Plan> {
+        if let Some(root) = self.read_write.iter().find(|path| path.parent().is_none()) {
+            let msg = format!("can’t sandbox with {} writable", root.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, msg));
+        }
+
+        let mut flags = CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS;
+        if !self.network {
+            flags |= CloneFlags::CLONE_NEWNET;
+        }
+
+        let mut mounts: Vec<(PathBuf, Mount)> = vec![];
+        for (paths, read_only) in [(&self.read_only, true), (&self.read_write, false)] {
+            for path in paths {
+                match fs::symlink_metadata(path) {
+                    Ok(meta) if meta.is_symlink() => {
+                        mounts.push((path.clone(), Mount::Symlink(fs::read_link(path)?)))
+                    }
+                    Ok(meta) => mounts.push((
+                        path.clone(),
+                        Mount::Bind {
+                            read_only,
+                            dir: meta.is_dir(),
+                        },
+                    )),
+                    // eg. `/lib32` on most systems
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+                    Err(err) => return Err(err),
+                }
+            }
+        }
+        for dev in ["/dev", "/proc"] {
+            mounts.push((
+                PathBuf::from(dev),
+                Mount::Bind {
+                    read_only: false,
+                    dir: true,
+                },
+            ));
+        }
+        mounts.push((PathBuf::from("/tmp"), Mount::Tmpfs));
+        // parents first so their children are mounted on top of them
+        mounts.sort_by_key(|(path, _)| path.components().count());
+
+        let mut steps = vec![];
+        let mut made: HashSet<PathBuf> = HashSet::new();
+        for (path, mount) in mounts {
+            let dst = self.root.join(path.strip_prefix("/").unwrap_or(&path));
+            for dir in dst
+                .ancestors()
+                .skip(1)
+                .collect::<Vec<_>>()
+                .into_iter()
+                .rev()
+            {
+                if dir.starts_with(&self.root) && dir != self.root && made.insert(dir.to_path_buf())
+                {
+                    steps.push(Step::Mkdir(cstring(dir)?));
+                }
+            }
+            match mount {
+                Mount::Symlink(target) => steps.push(Step::Symlink {
+                    target: cstring(&target)?,
+                    link: cstring(&dst)?,
+                }),
+                Mount::Bind { read_only, dir } => {
+                    if dir {
+                        made.insert(dst.clone());
+                        steps.push(Step::Mkdir(cstring(&dst)?));
+                    } else {
+                        steps.push(Step::Touch(cstring(&dst)?));
+                    }
+                    steps.push(Step::Bind {
+                        src: cstring(&path)?,
+                        dst: cstring(&dst)?,
+                        read_only,
+                    });
+                }
+                Mount::Tmpfs => {
+                    made.insert(dst.clone());
+                    steps.push(Step::Mkdir(cstring(&dst)?));
+                    steps.push(Step::Tmpfs(cstring(&dst)?));
+                }
+            }
+        }
+
+        // we are root in the sandbox but only as far as the namespace goes
+        let uid = unsafe { libc::getuid() };
+        let gid = unsafe { libc::getgid() };
+        Ok(Plan {
+            flags,
+            uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+            root: cstring(&self.root)?,
+            cwd: cstring(&std::env::current_dir()?)?,
+            steps,
+        })
+    }
+}
+
+enum Mount {
+    Bind { read_only: bool, dir: bool },
+    Symlink(PathBuf),
+    Tmpfs,
+}
+
+#[derive(Debug, PartialEq)]
+pub(crate) enum Step {
+    Mkdir(CString),
+    Touch(CString),
+    Symlink {
+        target: CString,
+        link: CString,
+    },
+    Bind {
+        src: CString,
+        dst: CString,
+        read_only: bool,
+    },
+    Tmpfs(CString),
+}
+
+// everything `enter` needs, prepared before we fork
+pub(crate) struct Plan {
+    flags: CloneFlags,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    root: CString,
+    cwd: CString,
+    pub(crate) steps: Vec<Step>,
+}
+
+impl Plan {
+    // runs in the forked child where allocating is unsafe
+    fn enter(&self) -> io::Result<()> {
+        unsafe {
+            unshare(self.flags)?;
+            write(c"/proc/self/setgroups", b"deny")?;
+            write(c"/proc/self/uid_map", &self.uid_map)?;
+            write(c"/proc/self/gid_map", &self.gid_map)?;
+
+            // don’t let our mounts propagate back out
+            check(libc::mount(
+                std::ptr::null(),
+                c"/".as_ptr(),
+                std::ptr::null(),
+                libc::MS_REC | libc::MS_PRIVATE,
+                std::ptr::null(),
+            ))?;
+            tmpfs(&self.root)?;
+
+            for step in &self.steps {
+                match step {
+                    Step::Mkdir(path) => {
+                        if libc::mkdir(path.as_ptr(), 0o755) != 0 {
+                            let err = io::Error::last_os_error();
+                            if err.kind() != io::ErrorKind::AlreadyExists {
+                                return Err(err);
+                            }
+                        }
+                    }
+                    Step::Touch(path) => {
+                        let fd = check(libc::open(
+                            path.as_ptr(),
+                            libc::O_CREAT | libc::O_WRONLY,
+                            0o644,
+                        ))?;
+                        libc::close(fd);
+                    }
+                    Step::Symlink { target, link } => {
+                        check(libc::symlink(target.as_ptr(), link.as_ptr()))?;
+                    }
+                    Step::Bind {
+                        src,
+                        dst,
+                        read_only,
+                    } => {
+                        bind(src, dst, *read_only)?;
+                    }
+                    Step::Tmpfs(path) => tmpfs(path)?,
+                }
+            }
+
+            // swap in the new root and detach the old one
+            check(libc::chdir(self.root.as_ptr()))?;
+            check(
+                libc::syscall(libc::SYS_pivot_root, c".".as_ptr(), c".".as_ptr()) as libc::c_int,
+            )?;
+            check(libc::umount2(c".".as_ptr(), libc::MNT_DETACH))?;
+            check(libc::chdir(self.cwd.as_ptr()))?;
+        }
+        Ok(())
+    }
+}
+
+unsafe fn bind(src: &CString, dst: &CString, read_only: bool) -> io::Result<()> {
+    let flags = libc::MS_BIND | libc::MS_REC;
+    check(libc::mount(
+        src.as_ptr(),
+        dst.as_ptr(),
+        std::ptr::null(),
+        flags,
+        std::ptr::null(),
+    ))?;
+    if !read_only {
+        return Ok(());
+    }
+
+    // remounting must keep the flags that are locked in a user namespace
+    let mut stat: libc::statvfs = std::mem::zeroed();
+    check(libc::statvfs(dst.as_ptr(), &mut stat))?;
+    let mut flags = libc::MS_BIND | libc::MS_REMOUNT | libc::MS_RDONLY;
+    for (st, ms) in [
+        (libc::ST_NOSUID, libc::MS_NOSUID),
+        (libc::ST_NODEV, libc::MS_NODEV),
+        (libc::ST_NOEXEC, libc::MS_NOEXEC),
+        (libc::ST_NOATIME, libc::MS_NOATIME),
+        (libc::ST_NODIRATIME, libc::MS_NODIRATIME),
+        (libc::ST_RELATIME, libc::MS_RELATIME),
+    ] {
+        if stat.f_flag & st != 0 {
+            flags |= ms;
+        }
+    }
+    check(libc::mount(
+        std::ptr::null(),
+        dst.as_ptr(),
+        std::ptr::null(),
+        flags,
+        std::ptr::null(),
+    ))?;
+    Ok(())
+}
+
+unsafe fn tmpfs(path: &CString) -> io::Result<()> {
+    check(libc::mount(
+        c"tmpfs".as_ptr(),
+        path.as_ptr(),
+        c"tmpfs".as_ptr(),
+        0,
+        std::ptr::null(),
+    ))?;
+    Ok(())
+}
+
+unsafe fn write(path: &std::ffi::CStr, content: &[u8]) -> io::Result<()> {
+    let fd = check(libc::open(path.as_ptr(), libc::O_WRONLY))?;
+    let written = libc::write(fd, content.as_ptr() as *const libc::c_void, content.len());
+    libc::close(fd);
+    if written < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn check(rv: libc::c_int) -> io::Result<libc::c_int> {
+    if rv < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(rv)
+    }
+}
+
+fn cstring(path: &Path) -> io::Result<CString> {
+    CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)
+}
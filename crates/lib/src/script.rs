@@ -0,0 +1,112 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+lazy_static! {
+    static ref DEPS_REGEX: Regex = Regex::new(r"^(?:#|//|--|;)+\s*pkgx:(.*)$").unwrap();
+}
+
+/// The interpreter line of the script at `path` (without the `#!`), if any.
+pub fn shebang(path: &Path) -> io::Result<Option<String>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0u8; 2];
+    if reader.read_exact(&mut magic).is_err() || &magic != b"#!" {
+        return Ok(None);
+    }
+    let mut line = vec![];
+    reader.read_until(b'\n', &mut line)?;
+    Ok(Some(String::from_utf8_lossy(&line).trim().to_string()))
+}
+
+/// The extension we look up interpreters by, eg. `py` for `./foo.py`.
+pub fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
+
+/// Packages declared in the script’s leading comments, eg. `# pkgx: +jq python@3.12`.
+pub fn deps(path: &Path) -> io::Result<Vec<String>> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(parse_deps(reader.lines().map_while(Result::ok)))
+}
+
+/// Reads `pkgx:` lines until the first line that is neither blank nor a comment.
+pub(crate) fn parse_deps<I, S>(lines: I) -> Vec<String>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut rv = vec![];
+    for line in lines {
+        let line = line.as_ref().trim();
+        if let Some(captures) = DEPS_REGEX.captures(line) {
+            rv.extend(
+                captures[1]
+                    .split_whitespace()
+                    .map(|pkgspec| pkgspec.trim_start_matches('+').to_string()),
+            );
+        } else if !(line.is_empty() || ["#", "//", "--", ";"].iter().any(|x| line.starts_with(x))) {
+            break;
+        }
+    }
+    rv
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Shebang {
+    /// packages to add, eg. `jq` for `#!/usr/bin/env -S pkgx +jq bash`
+    pub plus: Vec<String>,
+    /// eg. `python3`, `/bin/sh` or (if pkgx is the shebang) `python@3.9`
+    pub program: String,
+    /// arguments that precede the script path
+    pub args: Vec<String>,
+}
+
+/// Parses a shebang line (as returned by `shebang`), seeing through `env` and `pkgx`.
+pub fn parse_shebang(line: &str) -> Option<Shebang> {
+    let mut words: Vec<&str> = line.split_whitespace().collect();
+
+    if words.first().map(|word| basename(word)) == Some("env") {
+        words.remove(0);
+        // skip env’s own options and variable assignments
+        while let Some(word) = words.first() {
+            if *word == "-u" {
+                words.drain(..2.min(words.len()));
+            } else if word.starts_with('-') || word.contains('=') {
+                words.remove(0);
+            } else {
+                break;
+            }
+        }
+    }
+
+    let mut plus = vec![];
+    if words.first().map(|word| basename(word)) == Some("pkgx") {
+        words.remove(0);
+        while let Some(word) = words.first() {
+            if let Some(pkgspec) = word.strip_prefix('+') {
+                plus.push(pkgspec.to_string());
+            } else if !word.starts_with('-') {
+                break;
+            }
+            words.remove(0);
+        }
+    }
+
+    if words.is_empty() {
+        return None;
+    }
+    let program = words.remove(0).to_string();
+    let args = words.into_iter().map(|word| word.to_string()).collect();
+
+    Some(Shebang {
+        plus,
+        program,
+        args,
+    })
+}
+
+fn basename(path: &str) -> &str {
+    path.rsplit('/').next().unwrap_or(path)
+}
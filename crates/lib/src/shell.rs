@@ -0,0 +1,288 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// lists the variables we changed so we can undo it
+const KEYS: &str = "_PKGX_KEYS";
+/// prefixes the values we replaced
+const SAVED_PREFIX: &str = "_PKGX_SAVED_";
+/// identifies the dev environment the directory hook activated
+pub const DEV: &str = "_PKGX_DEV";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+    Posix,
+    Bash,
+    Zsh,
+    Fish,
+    Elvish,
+    Nushell,
+}
+
+impl Shell {
+    /// From `$SHELL`, falling back to POSIX.
+    pub fn detect() -> Self {
+        std::env::var("SHELL")
+            .ok()
+            .and_then(|shell| shell.rsplit('/').next()?.parse().ok())
+            .unwrap_or(Shell::Posix)
+    }
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sh" | "posix" | "dash" | "ash" | "ksh" => Ok(Shell::Posix),
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            "elvish" => Ok(Shell::Elvish),
+            "nu" | "nushell" => Ok(Shell::Nushell),
+            _ => Err(format!("unsupported shell: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Shell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Shell::Posix => "sh",
+            Shell::Bash => "bash",
+            Shell::Zsh => "zsh",
+            Shell::Fish => "fish",
+            Shell::Elvish => "elvish",
+            Shell::Nushell => "nu",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Change {
+    /// eg. `PATH`, these come before whatever the user already has
+    Prepend(Vec<String>),
+    /// eg. `SSL_CERT_FILE`, these replace whatever the user already has
+    Set(String),
+}
+
+/// `None` unsets the variable
+pub type Assignments = Vec<(String, Option<String>)>;
+
+/// Changes from the output of `env::map` and `env::mix_runtime`: the paths
+/// `env::map` computed are prepended, the rest replace what the user has.
+pub fn changes(
+    paths: &HashMap<String, Vec<String>>,
+    runtime: HashMap<String, String>,
+) -> Vec<(String, Change)> {
+    let mut rv: Vec<(String, Change)> = runtime
+        .into_iter()
+        .map(|(key, value)| {
+            let change = if paths.contains_key(&key) {
+                Change::Prepend(value.split(':').map(|x| x.to_string()).collect())
+            } else {
+                Change::Set(value)
+            };
+            (key, change)
+        })
+        .collect();
+    rv.sort_by(|a, b| a.0.cmp(&b.0));
+    rv
+}
+
+/// The assignments that apply `changes` to `current` (typically the
+/// process’s environment), recording what they replace for `deactivate`.
+///
+/// If `current` is already activated that is undone first.
+pub fn activate(current: &HashMap<String, String>, changes: &[(String, Change)]) -> Assignments {
+    let mut rv = deactivate(current);
+    let restored = apply(current, &rv);
+
+    for (key, change) in changes {
+        if let Some(value) = restored.get(key) {
+            rv.push((format!("{}{}", SAVED_PREFIX, key), Some(value.clone())));
+        }
+        let value = match change {
+            Change::Prepend(paths) => {
+                let mut paths = paths.clone();
+                if let Some(value) = restored.get(key).filter(|value| !value.is_empty()) {
+                    paths.push(value.clone());
+                }
+                paths.join(":")
+            }
+            Change::Set(value) => value.clone(),
+        };
+        rv.push((key.clone(), Some(value)));
+    }
+
+    if !changes.is_empty() {
+        let keys: Vec<&str> = changes.iter().map(|(key, _)| key.as_str()).collect();
+        rv.push((KEYS.to_string(), Some(keys.join(" "))));
+    }
+
+    squash(rv)
+}
+
+/// The assignments that restore `current` to how it was before `activate`.
+///
+/// `DEV` is always unset, so the hook activates again even if the rest of the
+/// env was lost.
+pub fn deactivate(current: &HashMap<String, String>) -> Assignments {
+    let mut rv = vec![];
+    let keys = current.get(KEYS).map(String::as_str).unwrap_or_default();
+    for key in keys.split_whitespace() {
+        let saved = format!("{}{}", SAVED_PREFIX, key);
+        match current.get(&saved) {
+            Some(value) => {
+                rv.push((key.to_string(), Some(value.clone())));
+                rv.push((saved, None));
+            }
+            None => rv.push((key.to_string(), None)),
+        }
+    }
+    if current.contains_key(KEYS) {
+        rv.push((KEYS.to_string(), None));
+    }
+    rv.push((DEV.to_string(), None));
+    rv
+}
+
+/// `assignments` without those that wouldn’t change `current`
+pub fn diff(current: &HashMap<String, String>, assignments: Assignments) -> Assignments {
+    assignments
+        .into_iter()
+        .filter(|(key, value)| current.get(key) != value.as_ref())
+        .collect()
+}
+
+/// `current` after `assignments`
+pub fn apply(
+    current: &HashMap<String, String>,
+    assignments: &Assignments,
+) -> HashMap<String, String> {
+    let mut rv = current.clone();
+    for (key, value) in assignments {
+        match value {
+            Some(value) => rv.insert(key.clone(), value.clone()),
+            None => rv.remove(key),
+        };
+    }
+    rv
+}
+
+// later assignments to the same key win, but keep the order of first appearance
+fn squash(assignments: Assignments) -> Assignments {
+    let mut rv: Assignments = vec![];
+    for (key, value) in assignments {
+        match rv.iter_mut().find(|(k, _)| *k == key) {
+            Some(existing) => existing.1 = value,
+            None => rv.push((key, value)),
+        }
+    }
+    rv
+}
+
+/// Shell code that performs `assignments`, for `eval` (or `source`).
+pub fn render(shell: Shell, assignments: &Assignments) -> String {
+    let mut rv = String::new();
+    for (key, value) in assignments {
+        let line = match (shell, value) {
+            (Shell::Posix | Shell::Bash | Shell::Zsh, Some(value)) => {
+                format!("export {}={}", key, posix_quote(value))
+            }
+            (Shell::Posix | Shell::Bash | Shell::Zsh, None) => format!("unset {}", key),
+            (Shell::Fish, Some(value)) if is_list(key) => {
+                let paths: Vec<String> = value.split(':').map(fish_quote).collect();
+                format!("set -gx --path {} {}", key, paths.join(" "))
+            }
+            (Shell::Fish, Some(value)) => format!("set -gx {} {}", key, fish_quote(value)),
+            (Shell::Fish, None) => format!("set -e {}", key),
+            (Shell::Elvish, Some(value)) => format!("set-env {} {}", key, elvish_quote(value)),
+            (Shell::Elvish, None) => format!("unset-env {}", key),
+            (Shell::Nushell, Some(value)) if key == "PATH" => {
+                let paths: Vec<String> = value.split(':').map(nushell_quote).collect();
+                format!("$env.{} = [{}]", key, paths.join(" "))
+            }
+            (Shell::Nushell, Some(value)) => format!("$env.{} = {}", key, nushell_quote(value)),
+            (Shell::Nushell, None) => format!("hide-env -i {}", key),
+        };
+        rv.push_str(&line);
+        rv.push('\n');
+    }
+    rv
+}
+
+/// Shell code that keeps the environment in sync with the nearest pkgx
+/// manifest as the user changes directory, for `eval` in the shell’s rc.
+pub fn hook(shell: Shell, pkgx: &Path) -> Result<String, String> {
+    let pkgx = pkgx.to_string_lossy();
+    let code = match shell {
+        Shell::Bash => format!(
+            r#"_pkgx_hook() {{
+  if [ "$PWD" != "${{_PKGX_PWD-}}" ]; then
+    _PKGX_PWD="$PWD"
+    eval "$({pkgx} --hook --shell=bash)"
+  fi
+}}
+case ";${{PROMPT_COMMAND-}};" in
+  *";_pkgx_hook;"*) ;;
+  *) PROMPT_COMMAND="_pkgx_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}" ;;
+esac
+_pkgx_hook
+"#,
+            pkgx = posix_quote(&pkgx)
+        ),
+        Shell::Zsh => format!(
+            r#"_pkgx_hook() {{
+  eval "$({pkgx} --hook --shell=zsh)"
+}}
+autoload -Uz add-zsh-hook
+add-zsh-hook chpwd _pkgx_hook
+_pkgx_hook
+"#,
+            pkgx = posix_quote(&pkgx)
+        ),
+        Shell::Fish => format!(
+            r#"function _pkgx_hook --on-variable PWD
+  {pkgx} --hook --shell=fish | source
+end
+_pkgx_hook
+"#,
+            pkgx = fish_quote(&pkgx)
+        ),
+        Shell::Elvish => format!(
+            r#"set after-chdir = [$@after-chdir {{|_| eval ({pkgx} --hook --shell=elvish | slurp) }}]
+eval ({pkgx} --hook --shell=elvish | slurp)
+"#,
+            pkgx = elvish_quote(&pkgx)
+        ),
+        Shell::Posix | Shell::Nushell => {
+            return Err(format!("no directory hook for {}", shell));
+        }
+    };
+    Ok(code)
+}
+
+// fish treats these as `:` separated lists
+fn is_list(key: &str) -> bool {
+    key.ends_with("PATH") || key.ends_with("DIRS")
+}
+
+fn posix_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn fish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\\', r"\\").replace('\'', r"\'"))
+}
+
+fn elvish_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+fn nushell_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', r"\\").replace('"', "\\\""))
+}
@@ -1,14 +1,16 @@
+use crate::Error;
 use crate::{config::Config, pantry_db};
 use async_compression::tokio::bufread::GzipDecoder;
 use fs2::FileExt;
 use futures::TryStreamExt;
 use rusqlite::Connection;
-use std::{error::Error, fs::OpenOptions, path::PathBuf};
+use std::{fs::OpenOptions, path::PathBuf, time::Instant};
 use tokio_tar::Archive;
 use tokio_util::compat::FuturesAsyncReadCompatExt;
+use tracing::debug;
 
 #[allow(clippy::all)]
-pub fn should(config: &Config) -> bool {
+pub fn should(config: &Config, conn: &Connection) -> bool {
     if !config.pantry_dir.join("projects").is_dir() {
         true
     } else if !config
@@ -19,12 +21,37 @@ pub fn should(config: &Config) -> bool {
         .is_file()
     {
         true
+    } else if !pantry_db::is_current(conn) {
+        true
     } else {
         false
     }
 }
 
-pub async fn replace(config: &Config, conn: &mut Connection) -> Result<(), Box<dyn Error>> {
+/// Rebuilds a pkg-db with other tables, ie. from another version of pkgx, from
+/// the pantry we already have rather than downloading it again.
+pub fn upgrade(config: &Config, conn: &mut Connection) -> Result<(), Error> {
+    let dest = &config.pantry_dir;
+    if !dest.join("projects").is_dir() || pantry_db::is_current(conn) {
+        return Ok(());
+    }
+
+    let dir = OpenOptions::new().read(true).open(dest)?;
+    debug!(path = %dest.display(), "waiting for pantry lock");
+    dir.lock_exclusive().map_err(|source| Error::Lock {
+        path: dest.clone(),
+        source,
+    })?;
+
+    let start = Instant::now();
+    pantry_db::cache(config, conn)?;
+    debug!(elapsed = ?start.elapsed(), "upgraded pantry.db");
+
+    FileExt::unlock(&dir)?;
+    Ok(())
+}
+
+pub async fn replace(config: &Config, conn: &mut Connection) -> Result<(), Error> {
     let url = env!("PKGX_PANTRY_TARBALL_URL");
     let dest = &config.pantry_dir;
 
@@ -32,25 +59,33 @@ pub async fn replace(config: &Config, conn: &mut Connection) -> Result<(), Box<d
     let dir = OpenOptions::new()
         .read(true) // Open in read-only mode; no need to write.
         .open(dest)?;
-    dir.lock_exclusive()?;
+    debug!(path = %dest.display(), "waiting for pantry lock");
+    dir.lock_exclusive().map_err(|source| Error::Lock {
+        path: dest.clone(),
+        source,
+    })?;
+    debug!(path = %dest.display(), "acquired pantry lock");
 
+    let start = Instant::now();
     download_and_extract_pantry(url, dest).await?;
+    debug!(elapsed = ?start.elapsed(), "extracted pantry");
 
+    let start = Instant::now();
     pantry_db::cache(config, conn)?;
+    debug!(elapsed = ?start.elapsed(), "cached pantry.db");
 
     FileExt::unlock(&dir)?;
 
     Ok(())
 }
 
-async fn download_and_extract_pantry(url: &str, dest: &PathBuf) -> Result<(), Box<dyn Error>> {
+async fn download_and_extract_pantry(url: &str, dest: &PathBuf) -> Result<(), Error> {
+    debug!(url, "fetching pantry");
     let rsp = reqwest::get(url).await?.error_for_status()?;
 
     let stream = rsp.bytes_stream();
 
-    let stream = stream
-        .map_err(|e| futures::io::Error::new(futures::io::ErrorKind::Other, e))
-        .into_async_read();
+    let stream = stream.map_err(futures::io::Error::other).into_async_read();
     let stream = stream.compat();
 
     let decoder = GzipDecoder::new(stream);
@@ -0,0 +1,51 @@
+use crate::detect::detect;
+use std::fs;
+
+#[test]
+fn test_detect() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-detect-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    fs::write(dir.join(".nvmrc"), "v20.11\n").unwrap();
+    fs::write(dir.join("package.json"), r#"{"engines": {"node": ">=18"}}"#).unwrap();
+    fs::write(dir.join(".python-version"), "# comment\n3.11\n").unwrap();
+    fs::write(
+        dir.join("rust-toolchain.toml"),
+        "[toolchain]\nchannel = \"1.75.0\"\n",
+    )
+    .unwrap();
+    fs::write(dir.join("go.mod"), "module foo\n\ngo 1.21\n").unwrap();
+    fs::write(dir.join(".ruby-version"), "ruby-3.2.2\n").unwrap();
+    fs::write(dir.join(".terraform-version"), "latest\n").unwrap();
+
+    let detected = detect(&dir)
+        .unwrap()
+        .into_iter()
+        .map(|(path, pkgreq)| {
+            let filename = path.file_name().unwrap().to_string_lossy().to_string();
+            (filename, pkgreq.to_string())
+        })
+        .collect::<Vec<_>>();
+
+    let broken = dir.join("broken");
+    fs::create_dir_all(&broken).unwrap();
+    fs::write(broken.join("package.json"), "{").unwrap();
+    fs::write(broken.join(".nvmrc"), "20\n").unwrap();
+    let broken = detect(&broken).unwrap();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(broken.len(), 1);
+
+    let expected = [
+        (".nvmrc", "nodejs.org~20.11"),
+        (".python-version", "python.org~3.11"),
+        ("rust-toolchain.toml", "rust-lang.org@1.75.0"),
+        ("go.mod", "go.dev>=1.21"),
+        (".ruby-version", "ruby-lang.org@3.2.2"),
+        (".terraform-version", "terraform.io"),
+    ]
+    .map(|(filename, pkgspec)| (filename.to_string(), pkgspec.to_string()));
+
+    assert_eq!(detected, expected);
+}
@@ -0,0 +1,315 @@
+use crate::config::Config;
+use crate::env::{
+    builtin_rules, expand, explain_paths, keep, map, mix_into, mix_runtime, overlay, values,
+    EnvRule, Source,
+};
+use crate::pantry_db;
+use crate::shell::{changes, Change};
+use crate::types::{Installation, Package};
+use libsemverator::semver::Semver as Version;
+use std::collections::HashMap;
+use std::fs;
+
+#[test]
+fn test_expand() {
+    let vars = HashMap::from([
+        ("FOO", "foo"),
+        ("PATH", "/a:/b"),
+        ("EMPTY", ""),
+        ("FOO_BAR", "foobar"),
+    ]);
+    let mut lookup = |name: &str| vars.get(name).map(|value| value.to_string());
+
+    let table = [
+        // plain text
+        ("/usr/bin", "/usr/bin"),
+        ("", ""),
+        ("a::b", "a::b"),
+        ("MANPATH:", "MANPATH:"),
+        // simple forms
+        ("$FOO", "foo"),
+        ("${FOO}", "foo"),
+        ("x${FOO}x", "xfoox"),
+        ("$FOO/bin", "foo/bin"),
+        ("$FOO_BAR", "foobar"),
+        ("${FOO}_BAR", "foo_BAR"),
+        ("$FOO$FOO", "foofoo"),
+        // lists
+        ("/x:$PATH", "/x:/a:/b"),
+        ("$PATH:/x", "/a:/b:/x"),
+        ("/x:$UNSET", "/x"),
+        ("$UNSET:/x", "/x"),
+        ("/x:$UNSET:/y", "/x:/y"),
+        ("/x:${UNSET}:$EMPTY:/y", "/x:/y"),
+        ("$UNSET", ""),
+        ("pre$UNSET", "pre"),
+        // defaults
+        ("${UNSET:-/d}", "/d"),
+        ("${EMPTY:-/d}", "/d"),
+        ("${FOO:-/d}", "foo"),
+        ("${UNSET:-/d:/e}:/x", "/d:/e:/x"),
+        ("${UNSET:-$FOO}", "foo"),
+        ("${UNSET:-${FOO}}", "foo"),
+        ("${UNSET:-}:/x", "/x"),
+        // not variables
+        ("$", "$"),
+        ("a$", "a$"),
+        ("$1", "$1"),
+        ("$-", "$-"),
+        ("${", "${"),
+        ("${1}", "${1}"),
+        ("${FOO", "${FOO"),
+        ("costs $5", "costs $5"),
+    ];
+
+    for (template, expected) in table {
+        assert_eq!(expand(template, &mut lookup), expected, "{}", template);
+    }
+}
+
+#[test]
+fn test_map() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-env-map-{}", std::process::id()));
+    let installation = |project: &str| {
+        let path = dir.join(project).join("v1.0.0");
+        Installation {
+            path,
+            pkg: Package {
+                project: project.to_string(),
+                version: Version::parse("1.0.0").unwrap(),
+            },
+        }
+    };
+    let python = installation("python.org");
+    let foo = installation("foo.org");
+    for subdir in [
+        "python.org/v1.0.0/bin",
+        "python.org/v1.0.0/lib/python3.11/site-packages",
+        "python.org/v1.0.0/lib/python3.12/site-packages",
+        "foo.org/v1.0.0/bin",
+        "foo.org/v1.0.0/share/man",
+        "foo.org/v1.0.0/share/foo",
+    ] {
+        fs::create_dir_all(dir.join(subdir)).unwrap();
+    }
+
+    let mut rules = builtin_rules();
+    let yaml = "FOO_PATH: share/foo\nBAR_PATH:\n  suffixes: [bin]\n  project: python.org\n";
+    let extra: HashMap<String, EnvRule> = serde_yaml::from_str(yaml).unwrap();
+    rules.extend(extra);
+
+    let installations = [python, foo];
+    let env = map(&installations, &rules);
+    let contributions = explain_paths(&installations, &rules);
+    let path = |suffix: &str| dir.join(suffix).to_string_lossy().to_string();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(
+        env["PATH"],
+        vec![path("python.org/v1.0.0/bin"), path("foo.org/v1.0.0/bin")]
+    );
+    assert_eq!(
+        env["PYTHONPATH"],
+        vec![
+            path("python.org/v1.0.0/lib/python3.11/site-packages"),
+            path("python.org/v1.0.0/lib/python3.12/site-packages"),
+        ]
+    );
+    assert_eq!(
+        env["MANPATH"],
+        vec![
+            path("foo.org/v1.0.0/share/man"),
+            "/usr/share/man".to_string()
+        ]
+    );
+    assert_eq!(env["FOO_PATH"], vec![path("foo.org/v1.0.0/share/foo")]);
+    assert_eq!(env["BAR_PATH"], vec![path("python.org/v1.0.0/bin")]);
+    assert!(!env.contains_key("CMAKE_PREFIX_PATH"));
+    assert!(!env.contains_key("CPATH"));
+
+    let sources: Vec<(&str, Source, Option<&str>)> = contributions
+        .iter()
+        .filter(|c| c.key == "MANPATH" || c.key == "PYTHONPATH")
+        .map(|c| {
+            let project = c.pkg.as_ref().map(|pkg| pkg.project.as_str());
+            (c.key.as_str(), c.source.clone(), project)
+        })
+        .collect();
+    let suffix = Source::Suffix("lib/python*/site-packages".to_string());
+    assert_eq!(
+        sources,
+        vec![
+            ("PYTHONPATH", suffix.clone(), Some("python.org")),
+            ("PYTHONPATH", suffix, Some("python.org")),
+            (
+                "MANPATH",
+                Source::Suffix("share/man".to_string()),
+                Some("foo.org")
+            ),
+            ("MANPATH", Source::Default, None),
+        ]
+    );
+}
+
+// a stand-in for `std::env::vars()`, which other tests share
+fn vars() -> Vec<(String, String)> {
+    [
+        ("TERM", "xterm"),
+        ("LC_ALL", "C"),
+        ("PATH", "/usr/bin:/bin"),
+        ("PKGX_TEST_INHERITED", "leaked"),
+    ]
+    .iter()
+    .map(|(key, value)| (key.to_string(), value.to_string()))
+    .collect()
+}
+
+#[test]
+fn test_pure() {
+    let input = HashMap::from([
+        (
+            "PATH".to_string(),
+            vec!["/a/bin".to_string(), "/b/bin".to_string()],
+        ),
+        ("FOO".to_string(), vec!["bar".to_string()]),
+    ]);
+    let env = overlay(keep(vars(), true), input);
+
+    assert_eq!(env["TERM"], "xterm");
+    assert_eq!(env["LC_ALL"], "C");
+    assert!(!env.contains_key("PKGX_TEST_INHERITED"));
+    // nothing of the system PATH survives
+    assert_eq!(env["PATH"], "/a/bin:/b/bin");
+    assert_eq!(env["FOO"], "bar");
+
+    assert!(!overlay(keep(vars(), true), HashMap::new()).contains_key("PATH"));
+    assert_eq!(keep(vars(), false).len(), vars().len());
+}
+
+// `yaml` as foo.org’s `runtime.env`, with it installed at `/opt/foo.org/v1.0.0`
+fn runtime_env(yaml: &str, name: &str) -> (rusqlite::Connection, [Installation; 1]) {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-env-{}-{}", name, std::process::id()));
+    let config = Config {
+        pantry_dir: dir.join("pantry"),
+        dist_url: "http://localhost:0".to_string(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    };
+    let entry = config.pantry_dir.join("projects/foo.org");
+    fs::create_dir_all(&entry).unwrap();
+    fs::write(
+        entry.join("package.yml"),
+        format!("runtime:\n  env:\n{}", yaml),
+    )
+    .unwrap();
+    let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+    pantry_db::cache(&config, &mut conn).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let installations = [Installation {
+        path: "/opt/foo.org/v1.0.0".into(),
+        pkg: Package {
+            project: "foo.org".to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+        },
+    }];
+    (conn, installations)
+}
+
+#[test]
+fn test_mix_runtime_pure() {
+    let yaml = "    FOO_FLAGS: '${PKGX_TEST_INHERITED:-none} -I{{prefix}}'\n";
+    let (conn, installations) = runtime_env(yaml, "pure");
+    let runtime = |input: &HashMap<String, String>, base| {
+        let env = mix_runtime(input, &base, &installations, &conn).unwrap();
+        env["FOO_FLAGS"].clone()
+    };
+
+    let env = runtime(&keep(vars(), false), keep(vars(), false));
+    assert_eq!(env, "leaked -I/opt/foo.org/v1.0.0");
+    let env = runtime(&keep(vars(), true), keep(vars(), true));
+    assert_eq!(env, "none -I/opt/foo.org/v1.0.0");
+    // the shell’s env, for activation, is what was inherited
+    let env = runtime(&HashMap::new(), keep(vars(), false));
+    assert_eq!(env, "leaked -I/opt/foo.org/v1.0.0");
+}
+
+#[test]
+fn test_mix_runtime_same_pass() {
+    // sorted `AAA` comes first but needs `ZZZ`
+    let yaml = "    AAA: '$ZZZ/x:$PATH'\n    ZZZ: '{{prefix}}/z'\n";
+    let (conn, installations) = runtime_env(yaml, "same-pass");
+    let base = keep(vars(), false);
+
+    let env = mix_runtime(&HashMap::new(), &base, &installations, &conn).unwrap();
+    assert_eq!(env["ZZZ"], "/opt/foo.org/v1.0.0/z");
+    assert_eq!(env["AAA"], "/opt/foo.org/v1.0.0/z/x:/usr/bin:/bin");
+}
+
+#[test]
+fn test_builtin_rules() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-env-builtin-{}", std::process::id()));
+    let installation = |project: &str| {
+        let path = dir.join(project).join("v1.0.0");
+        fs::create_dir_all(&path).unwrap();
+        Installation {
+            path,
+            pkg: Package {
+                project: project.to_string(),
+                version: Version::parse("1.0.0").unwrap(),
+            },
+        }
+    };
+    let jdk = installation("openjdk.org");
+    let foo = installation("foo.org");
+    fs::create_dir_all(foo.path.join("share/go")).unwrap();
+    let go = installation("go.dev");
+
+    let rules = builtin_rules();
+    let without_go = map(&[jdk.clone(), foo.clone()], &rules);
+    let env = map(&[jdk.clone(), foo.clone(), go.clone()], &rules);
+    let values = values(&[jdk.clone(), foo.clone(), go], &rules);
+    let path = |installation: &Installation, suffix: &str| {
+        installation.path.join(suffix).to_string_lossy().to_string()
+    };
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    // only openjdk.org’s prefix, not every installation’s, and not a list
+    let java_home = jdk.path.to_string_lossy().to_string();
+    assert!(!env.contains_key("JAVA_HOME"));
+    assert_eq!(values["JAVA_HOME"], java_home);
+    // replacing what the user has
+    let vars = [("JAVA_HOME".to_string(), "/old/jdk".to_string())];
+    let mut mixed = mix_into(vars, env.clone());
+    mixed.extend(values.clone());
+    assert_eq!(mixed["JAVA_HOME"], java_home);
+    let changes = changes(&env, values);
+    assert!(changes.contains(&("JAVA_HOME".to_string(), Change::Set(java_home))));
+    assert_eq!(env["GOPATH"], vec![path(&foo, "share/go")]);
+    assert!(!without_go.contains_key("GOPATH"));
+
+    let yaml = "FOO_HOME:\n  suffixes: [.]\n  replace: true\n";
+    let rules: HashMap<String, EnvRule> = serde_yaml::from_str(yaml).unwrap();
+    assert!(rules["FOO_HOME"].replace);
+
+    // a typo isn’t silently ignored
+    let typo = "FOO_PATH:\n  suffixes: [lib]\n  require: foo.org\n";
+    assert!(serde_yaml::from_str::<HashMap<String, EnvRule>>(typo).is_err());
+}
+
+#[test]
+fn test_source_json() {
+    let json = |source| serde_json::to_value(source).unwrap();
+    assert_eq!(
+        json(Source::Suffix("bin".to_string())),
+        serde_json::json!({ "kind": "suffix", "value": "bin" })
+    );
+    assert_eq!(
+        json(Source::RuntimeEnv),
+        serde_json::json!({ "kind": "runtime-env" })
+    );
+}
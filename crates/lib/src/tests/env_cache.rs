@@ -0,0 +1,38 @@
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use crate::env_cache::{key, prune};
+use crate::manifest::Manifest;
+use crate::types::PackageReq;
+
+#[test]
+fn test_key() {
+    let manifest = Manifest {
+        paths: vec![],
+        deps: vec![PackageReq::parse("node@20").unwrap()],
+    };
+    // a cache key that changed with the build of pkgx would be useless
+    assert_eq!(key(&manifest).unwrap(), "e8bd1c5ad5f208f0");
+}
+
+#[test]
+fn test_prune() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-env-cache-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let now = SystemTime::now();
+    for (name, age) in [("a.json", 3), ("b.json", 1), ("c.json", 2)] {
+        let file = fs::File::create(dir.join(name)).unwrap();
+        file.set_modified(now - Duration::from_secs(age)).unwrap();
+    }
+
+    prune(&dir, 2).unwrap();
+    let mut left: Vec<String> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    left.sort();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(left, vec!["b.json", "c.json"]);
+}
@@ -0,0 +1,34 @@
+use crate::{hydrate::hydrate, manifest::parse, types::PackageReq, which::WhichError, Error};
+
+#[test]
+fn test_code() {
+    let err = PackageReq::parse("node@x.y").unwrap_err();
+    assert_eq!(err.code(), "invalid");
+    assert_eq!(parse("dependencies: [").unwrap_err().code(), "invalid");
+
+    let pkgs = vec![
+        PackageReq::parse("node@18").unwrap(),
+        PackageReq::parse("node@20").unwrap(),
+    ];
+    let err = futures::executor::block_on(hydrate(&pkgs, |_| Ok(vec![]))).unwrap_err();
+    assert_eq!(err.code(), "conflict");
+    assert!(err.to_string().starts_with("node: "));
+
+    let err = Error::from(WhichError::CmdNotFound("foo".to_string()));
+    assert_eq!(err.code(), "not-found");
+    assert_eq!(err.to_string(), "not found: foo");
+    let err = Error::from(WhichError::MultipleProjects("foo".to_string(), vec![]));
+    assert_eq!(err.code(), "conflict");
+    assert!(!err.is_transient());
+
+    let network = |status, transient| Error::Network {
+        url: "https://dist.pkgx.dev".to_string(),
+        status,
+        reason: String::new(),
+        transient,
+    };
+    assert!(network(None, true).is_transient());
+    assert!(!network(None, false).is_transient());
+    assert!(network(Some(503), false).is_transient());
+    assert!(!network(Some(404), false).is_transient());
+}
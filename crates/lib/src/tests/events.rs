@@ -0,0 +1,26 @@
+use libsemverator::semver::Semver as Version;
+use serde_json::json;
+
+use crate::{events::Event, types::Package};
+
+#[test]
+fn test_json() {
+    let pkg = Package {
+        project: "foo.org".to_string(),
+        version: Version::parse("1.2.3").unwrap(),
+    };
+    assert_eq!(
+        json!(Event::DownloadStarted {
+            pkg: pkg.clone(),
+            size: None
+        }),
+        json!({ "event": "download-started", "pkg": { "project": "foo.org", "version": "1.2.3" }, "size": null })
+    );
+    assert_eq!(
+        json!(Event::Failed {
+            pkg,
+            err: "oops".to_string()
+        })["event"],
+        "failed"
+    );
+}
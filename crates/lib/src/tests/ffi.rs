@@ -0,0 +1,96 @@
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+use crate::{
+    ffi::{
+        pkgx_free, pkgx_last_error, pkgx_new, pkgx_string_free, pkgx_which, PkgxHandle, PkgxStatus,
+    },
+    types::Package,
+    Error, Pkgx,
+};
+
+use super::pkgx::fixture;
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(pkgx_last_error()) }
+        .to_string_lossy()
+        .to_string()
+}
+
+#[test]
+fn test_status() {
+    let io = || Error::Io(std::io::Error::other("oops"));
+    assert_eq!(
+        PkgxStatus::from(&Error::NotFound("foo".to_string())),
+        PkgxStatus::NotFound
+    );
+    assert_eq!(
+        PkgxStatus::from(&Error::Checksum { url: String::new() }),
+        PkgxStatus::Checksum
+    );
+    assert_eq!(PkgxStatus::from(&io()), PkgxStatus::Io);
+    assert_eq!(
+        PkgxStatus::from(&Error::pantry("foo.org", "bad")),
+        PkgxStatus::Pantry
+    );
+
+    // the first failure’s
+    let pkg = Package {
+        project: "foo.org".to_string(),
+        version: libsemverator::semver::Semver::parse("1.0.0").unwrap(),
+    };
+    let failures = vec![
+        (pkg.clone(), Error::Conflict("foo".to_string())),
+        (pkg, io()),
+    ];
+    assert_eq!(
+        PkgxStatus::from(&Error::Install(failures)),
+        PkgxStatus::Conflict
+    );
+    assert_eq!(PkgxStatus::from(&Error::Install(vec![])), PkgxStatus::Io);
+}
+
+#[test]
+fn test_ffi() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-ffi-{}", std::process::id()));
+    let config = fixture(&dir);
+    let handle = PkgxHandle::new(Pkgx::new(config).unwrap()).unwrap();
+    let handle = Box::into_raw(Box::new(handle));
+    let mut out: *mut c_char = ptr::null_mut();
+
+    // the result is JSON and ours to free
+    let status = unsafe { pkgx_which(handle, c"foo".as_ptr(), &mut out) };
+    assert_eq!(status, PkgxStatus::Ok);
+    assert_eq!(last_error(), "");
+    let json = unsafe { CStr::from_ptr(out) }.to_str().unwrap().to_string();
+    unsafe { pkgx_string_free(out) };
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({ "project": "foo.org", "path": "bin/foo" })
+    );
+
+    // failures leave `out` null and say why
+    out = c"garbage".as_ptr() as *mut c_char;
+    let status = unsafe { pkgx_which(handle, c"bar".as_ptr(), &mut out) };
+    assert_eq!(status, PkgxStatus::NotFound);
+    assert!(out.is_null());
+    assert_eq!(last_error(), "not found: bar");
+
+    // nulls are invalid rather than crashes
+    let status = unsafe { pkgx_which(ptr::null_mut(), c"foo".as_ptr(), &mut out) };
+    assert_eq!(status, PkgxStatus::Invalid);
+    assert_eq!(last_error(), "null handle");
+    let status = unsafe { pkgx_which(handle, ptr::null(), &mut out) };
+    assert_eq!(status, PkgxStatus::Invalid);
+    let status = unsafe { pkgx_which(handle, c"foo".as_ptr(), ptr::null_mut()) };
+    assert_eq!(status, PkgxStatus::Invalid);
+    assert_eq!(unsafe { pkgx_new(ptr::null_mut()) }, PkgxStatus::Invalid);
+    unsafe {
+        pkgx_string_free(ptr::null_mut());
+        pkgx_free(ptr::null_mut());
+        pkgx_free(handle);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,251 @@
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use async_compression::tokio::bufread::XzEncoder;
+use libsemverator::semver::Semver as Version;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    config::Config,
+    events::{self, Event},
+    install::{install, partial_path},
+    types::Package,
+};
+
+// `foo.org/v1.0.0/bin/foo` as a `.tar.xz`
+async fn bottle() -> Vec<u8> {
+    let mut tar = tokio_tar::Builder::new(vec![]);
+    let mut header = tokio_tar::Header::new_gnu();
+    header.set_size(3);
+    header.set_mode(0o755);
+    header.set_cksum();
+    tar.append_data(&mut header, "foo.org/v1.0.0/bin/foo", &b"foo"[..])
+        .await
+        .unwrap();
+    let tar = tar.into_inner().await.unwrap();
+    let mut rv = vec![];
+    XzEncoder::new(&tar[..]).read_to_end(&mut rv).await.unwrap();
+    rv
+}
+
+// what the client asked for the bottle with
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Request {
+    range: Option<String>,
+    if_range: Option<String>,
+}
+
+// serves `bottle` honoring `Range`, recording each request for it. If `cut`
+// the first response stops halfway.
+fn serve(bottle: Vec<u8>, checksum: String, cut: bool) -> (String, Arc<Mutex<Vec<Request>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Arc::new(Mutex::new(vec![]));
+    let recorded = requests.clone();
+    std::thread::spawn(move || {
+        let mut cut = cut;
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut lines = BufReader::new(&stream).lines().map(Result::unwrap);
+            let path = lines.next().unwrap().split(' ').nth(1).unwrap().to_string();
+            let mut rq = Request::default();
+            for line in lines.take_while(|line| !line.is_empty()) {
+                if let Some(range) = line.strip_prefix("range: bytes=") {
+                    rq.range = Some(range.to_string());
+                } else if let Some(validator) = line.strip_prefix("if-range: ") {
+                    rq.if_range = Some(validator.to_string());
+                }
+            }
+            if path.ends_with(".sha256sum") {
+                let body = format!("{}  bottle.tar.xz\n", checksum);
+                let head = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(body.as_bytes()).unwrap();
+                continue;
+            }
+
+            recorded.lock().unwrap().push(rq.clone());
+            let from = match rq.range {
+                Some(range) => range.trim_end_matches('-').parse().unwrap(),
+                None => 0,
+            };
+            let mut head = if from > 0 {
+                let range = format!("bytes {}-{}/{}", from, bottle.len() - 1, bottle.len());
+                format!(
+                    "HTTP/1.1 206 Partial Content\r\ncontent-range: {}\r\n",
+                    range
+                )
+            } else {
+                "HTTP/1.1 200 OK\r\n".to_string()
+            };
+            head.push_str("etag: \"v1\"\r\nconnection: close\r\n");
+            let body = &bottle[from..];
+            if cut {
+                // chunked so stopping short is an error rather than the end
+                cut = false;
+                let half = &body[..body.len() / 2];
+                head.push_str("transfer-encoding: chunked\r\n\r\n");
+                stream.write_all(head.as_bytes()).unwrap();
+                write!(stream, "{:x}\r\n", half.len()).unwrap();
+                stream.write_all(half).unwrap();
+                stream.write_all(b"\r\n").unwrap();
+            } else {
+                head.push_str(&format!("content-length: {}\r\n\r\n", body.len()));
+                stream.write_all(head.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        }
+    });
+    (url, requests)
+}
+
+fn config(dir: &Path, dist_url: String) -> Config {
+    Config {
+        pantry_dir: dir.join("cache/pantry"),
+        dist_url,
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    }
+}
+
+fn range(range: String) -> Request {
+    Request {
+        range: Some(range),
+        if_range: None,
+    }
+}
+
+#[tokio::test]
+async fn test_install_resumes() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-install-{}", std::process::id()));
+    let bottle = bottle().await;
+    let checksum = format!("{:x}", Sha256::digest(&bottle));
+    let pkg = Package {
+        project: "foo.org".to_string(),
+        version: Version::parse("1.0.0").unwrap(),
+    };
+
+    // interrupted halfway last time
+    let (url, requests) = serve(bottle.clone(), checksum.clone(), false);
+    let config = config(&dir, url);
+    let partial = partial_path(&pkg, &config);
+    fs::create_dir_all(partial.parent().unwrap()).unwrap();
+    let half = bottle.len() / 2;
+    fs::write(&partial, &bottle[..half]).unwrap();
+
+    let installation = install(&pkg, &config, None).await.unwrap();
+    let foo = fs::read_to_string(installation.path.join("bin/foo")).unwrap();
+    let resumed = requests.lock().unwrap().clone();
+    assert_eq!(resumed, vec![range(format!("{}-", half))]);
+    assert!(!partial.exists());
+
+    // a resumed download that isn’t what it should be starts over, once
+    let (url, requests) = serve(bottle.clone(), checksum, false);
+    let config = self::config(&dir.join("corrupt"), url);
+    let partial = partial_path(&pkg, &config);
+    fs::create_dir_all(partial.parent().unwrap()).unwrap();
+    fs::write(&partial, vec![0; half]).unwrap();
+    let installation = install(&pkg, &config, None).await.unwrap();
+    let again = fs::read_to_string(installation.path.join("bin/foo")).unwrap();
+    let discarded = !partial.exists();
+    let restarted = requests.lock().unwrap().clone();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(foo, "foo");
+    assert_eq!(again, "foo");
+    assert!(discarded);
+    assert_eq!(
+        restarted,
+        vec![range(format!("{}-", half)), Request::default()]
+    );
+}
+
+#[tokio::test]
+async fn test_install_resumes_cut_off() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-cut-{}", std::process::id()));
+    let bottle = bottle().await;
+    let checksum = format!("{:x}", Sha256::digest(&bottle));
+    let pkg = Package {
+        project: "foo.org".to_string(),
+        version: Version::parse("1.0.0").unwrap(),
+    };
+
+    let (url, requests) = serve(bottle.clone(), checksum, true);
+    let config = Config {
+        retries: 1,
+        ..config(&dir, url)
+    };
+    let installation = install(&pkg, &config, None).await.unwrap();
+    let foo = fs::read_to_string(installation.path.join("bin/foo")).unwrap();
+    let partial = partial_path(&pkg, &config);
+    let cleaned = !partial.exists() && !partial.with_extension("partial.validator").exists();
+    let requests = requests.lock().unwrap().clone();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(foo, "foo");
+    assert!(cleaned);
+    // the second attempt picks up where the first was cut off
+    let half = bottle.len() / 2;
+    let resumed = Request {
+        range: Some(format!("{}-", half)),
+        if_range: Some("\"v1\"".to_string()),
+    };
+    assert_eq!(requests, vec![Request::default(), resumed]);
+}
+
+#[tokio::test]
+async fn test_install_events() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-events-{}", std::process::id()));
+    let bottle = bottle().await;
+    let checksum = format!("{:x}", Sha256::digest(&bottle));
+    let pkg = Package {
+        project: "foo.org".to_string(),
+        version: Version::parse("1.0.0").unwrap(),
+    };
+    // the events’ names, a run of chunks as one
+    let names = |rx: &mut events::Receiver| {
+        let mut rv: Vec<String> = vec![];
+        while let Ok(event) = rx.try_recv() {
+            let json = serde_json::to_value(&event).unwrap();
+            let name = json["event"].as_str().unwrap().to_string();
+            if rv.last() != Some(&name) || !matches!(event, Event::Chunk { .. }) {
+                rv.push(name);
+            }
+        }
+        rv
+    };
+    let expected = ["download-started", "chunk", "extracting", "installed"];
+
+    // small enough to stream
+    let (url, _) = serve(bottle.clone(), checksum.clone(), false);
+    let config = config(&dir.join("stream"), url);
+    let (tx, mut rx) = events::channel();
+    install(&pkg, &config, Some(tx)).await.unwrap();
+    let streamed = names(&mut rx);
+
+    // resumed from a partial download
+    let (url, _) = serve(bottle.clone(), checksum, false);
+    let config = self::config(&dir.join("resume"), url);
+    let partial = partial_path(&pkg, &config);
+    fs::create_dir_all(partial.parent().unwrap()).unwrap();
+    fs::write(&partial, &bottle[..bottle.len() / 2]).unwrap();
+    let (tx, mut rx) = events::channel();
+    install(&pkg, &config, Some(tx)).await.unwrap();
+    let resumed = names(&mut rx);
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(streamed, expected);
+    assert_eq!(resumed, expected);
+}
@@ -0,0 +1,93 @@
+use libsemverator::semver::Semver as Version;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use crate::{
+    config::Config,
+    events::{self, Event},
+    install_multi::{install_each, install_multi},
+    types::{Installation, Package},
+    Error,
+};
+
+#[tokio::test]
+async fn test_install_multi() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-install-multi-{}", std::process::id()));
+    let config = Config {
+        pantry_dir: dir.join("cache/pantry"),
+        // nothing listens here so every download fails
+        dist_url: "http://127.0.0.1:9".to_string(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 1,
+    };
+    let pkgs: Vec<Package> = ["foo.org", "bar.org"]
+        .iter()
+        .map(|project| Package {
+            project: project.to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+        })
+        .collect();
+
+    let (tx, mut rx) = events::channel();
+    let err = install_multi(&pkgs, &config, Some(tx)).await.unwrap_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // the first failure didn’t stop the second
+    let Error::Install(failures) = &err else {
+        panic!("{:?}", err);
+    };
+    assert_eq!(failures.len(), 2);
+    assert!(failures.iter().all(|(_, err)| err.is_transient()));
+    assert_eq!(err.code(), "network");
+
+    let mut failed = 0;
+    while let Some(event) = rx.recv().await {
+        if let Event::Failed { .. } = event {
+            failed += 1;
+        }
+    }
+    assert_eq!(failed, 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_install_each() {
+    let config = Config {
+        pantry_dir: "/nonexistent/cache/pantry".into(),
+        dist_url: String::new(),
+        pkgx_dir: "/nonexistent/pkgx".into(),
+        config_dir: "/nonexistent/config".into(),
+        jobs: 2,
+        retries: 0,
+    };
+    let pkgs: Vec<Package> = (0..5)
+        .map(|n| Package {
+            project: format!("{}.org", n),
+            version: Version::parse("1.0.0").unwrap(),
+        })
+        .collect();
+
+    // how many are installing now, and at most
+    let running = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+    let installations = install_each(&pkgs, &config, None, |pkg, config, _| {
+        let (running, peak) = (&running, &peak);
+        async move {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            peak.fetch_max(now, Ordering::SeqCst);
+            // the clock is paused so this only lets the others run
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            running.fetch_sub(1, Ordering::SeqCst);
+            Ok(Installation {
+                path: config.pkgx_dir.join(&pkg.project),
+                pkg: pkg.clone(),
+            })
+        }
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(installations.len(), 5);
+    assert_eq!(peak.load(Ordering::SeqCst), 2);
+}
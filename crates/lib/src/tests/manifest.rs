@@ -0,0 +1,38 @@
+use crate::manifest::{parse, Manifest};
+use crate::types::PackageReq;
+
+fn pkgspecs(content: &str) -> Option<Vec<String>> {
+    parse(content)
+        .unwrap()
+        .map(|deps| deps.iter().map(|dep| dep.to_string()).collect())
+}
+
+#[test]
+fn test_parse() {
+    let expected = Some(vec!["node^20".to_string(), "python~3.11".to_string()]);
+    assert_eq!(pkgspecs("dependencies: node^20 python~3.11"), expected);
+    assert_eq!(pkgspecs("dependencies: [node^20, python~3.11]"), expected);
+    assert_eq!(
+        pkgspecs("dependencies:\n  python: ~3.11\n  node: 20\n"),
+        expected
+    );
+    assert_eq!(pkgspecs("dependencies: jq"), Some(vec!["jq".to_string()]));
+
+    assert_eq!(pkgspecs(""), None);
+    assert_eq!(pkgspecs("env:\n  FOO: bar\n"), None);
+    assert!(parse("dependencies: node@x.y").is_err());
+}
+
+#[test]
+fn test_dedup() {
+    let mut manifest = Manifest {
+        paths: vec![],
+        deps: ["node@20", "python~3.11", "nodejs.org@18"]
+            .iter()
+            .map(|pkgspec| PackageReq::parse(pkgspec).unwrap())
+            .collect(),
+    };
+    manifest.dedup(|dep| (dep.project == "node").then(|| "nodejs.org".to_string()));
+    let deps: Vec<String> = manifest.deps.iter().map(|dep| dep.to_string()).collect();
+    assert_eq!(deps, vec!["node^20", "python~3.11"]);
+}
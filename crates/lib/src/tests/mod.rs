@@ -0,0 +1,20 @@
+mod detect;
+mod env;
+mod env_cache;
+mod error;
+mod events;
+mod ffi;
+mod install;
+mod install_multi;
+mod manifest;
+mod moustache;
+mod pantry_db;
+mod pkgx;
+mod resolve;
+mod retry;
+mod run;
+#[cfg(target_os = "linux")]
+mod sandbox;
+mod script;
+mod shell;
+mod which;
@@ -0,0 +1,46 @@
+use crate::moustache::{render, MoustacheError};
+use std::collections::HashMap;
+
+#[test]
+fn test_render() {
+    let vars: HashMap<String, String> = [
+        ("prefix", "/pkgx/foo.org/v1.2.3"),
+        ("version.marketing", "1.2"),
+        ("deps.bar.org.prefix", "/pkgx/bar.org/v2.0.0"),
+    ]
+    .iter()
+    .map(|(k, v)| (k.to_string(), v.to_string()))
+    .collect();
+
+    let table = [
+        ("plain", "plain"),
+        ("{{prefix}}/bin", "/pkgx/foo.org/v1.2.3/bin"),
+        ("${{prefix}}/bin", "/pkgx/foo.org/v1.2.3/bin"),
+        ("${{ prefix }}/bin", "/pkgx/foo.org/v1.2.3/bin"),
+        ("lib/{{version.marketing}}:$FOO", "lib/1.2:$FOO"),
+        ("{{deps.bar.org.prefix}}/lib", "/pkgx/bar.org/v2.0.0/lib"),
+        ("{ {prefix} }", "{ {prefix} }"),
+        ("${FOO}", "${FOO}"),
+        ("$${{prefix}}", "$$/pkgx/foo.org/v1.2.3"),
+        ("\\${{prefix}}", "\\$/pkgx/foo.org/v1.2.3"),
+        ("$ {{prefix}}", "$ /pkgx/foo.org/v1.2.3"),
+    ];
+    for (template, expected) in table {
+        assert_eq!(render(template, &vars).unwrap(), expected, "{}", template);
+    }
+
+    assert_eq!(
+        render("{{deps.baz.org.prefix}}", &vars),
+        Err(MoustacheError::Unresolved(
+            "deps.baz.org.prefix".to_string()
+        ))
+    );
+    assert_eq!(
+        render("{{nope}}", &vars),
+        Err(MoustacheError::Unresolved("nope".to_string()))
+    );
+    assert_eq!(
+        render("{{prefix", &vars),
+        Err(MoustacheError::Unterminated("{{prefix".to_string()))
+    );
+}
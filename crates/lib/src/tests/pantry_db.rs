@@ -0,0 +1,67 @@
+use crate::config::Config;
+use crate::pantry_db::{self, match_versioned};
+use crate::sync;
+use libsemverator::range::Range as VersionReq;
+use rusqlite::Connection;
+
+#[test]
+fn test_match_versioned() {
+    let cases = [
+        ("python{{version.marketing}}", "python3.11", Some("~3.11")),
+        ("python{{version.major}}", "python3", Some("^3")),
+        ("python{{ version.major }}", "python3", Some("^3")),
+        (
+            "python{{version.major}}.{{version.minor}}",
+            "python3.11",
+            Some("~3.11"),
+        ),
+        ("foo-{{version}}", "foo-1.2.3", Some("@1.2.3")),
+        ("python{{version.marketing}}", "python3", None),
+        ("python{{version.marketing}}", "python", None),
+        ("python{{version.marketing}}", "ruby3.11", None),
+        ("python{{version.minor}}", "python11", None),
+        ("python", "python", None),
+    ];
+
+    for (template, cmd, expected) in cases {
+        let expected = expected.map(|raw| VersionReq::parse(raw).unwrap());
+        assert_eq!(
+            match_versioned(template, cmd),
+            expected,
+            "{} ~ {}",
+            template,
+            cmd
+        );
+    }
+}
+
+#[test]
+fn test_upgrade() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-upgrade-{}", std::process::id()));
+    let config = Config {
+        pantry_dir: dir.join("pantry"),
+        dist_url: "http://localhost:0".to_string(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    };
+    let entry = config.pantry_dir.join("projects/foo.org");
+    std::fs::create_dir_all(&entry).unwrap();
+    std::fs::write(entry.join("package.yml"), "provides: [bin/foo]\n").unwrap();
+
+    // as an older pkgx left it
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "user_version", 1).unwrap();
+    assert!(!pantry_db::is_current(&conn));
+
+    // rebuilt from the pantry on disk, nothing is downloaded
+    sync::upgrade(&config, &mut conn).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(pantry_db::is_current(&conn));
+    assert_eq!(
+        pantry_db::programs_for_project("foo.org", &conn).unwrap(),
+        vec!["bin/foo"]
+    );
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use rusqlite::Connection;
+
+use crate::{config::Config, events::Event, pantry_db, types::PackageReq, Pkgx};
+
+// a pantry with `foo.org`, which provides `foo`, already installed
+pub(super) fn fixture(dir: &Path) -> Config {
+    let config = Config {
+        pantry_dir: dir.join("cache/pantry"),
+        dist_url: "http://localhost:0".to_string(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    };
+
+    let entry = config.pantry_dir.join("projects/foo.org");
+    fs::create_dir_all(&entry).unwrap();
+    let yaml = "provides: [bin/foo]\nruntime:\n  env:\n    FOO_HOME: '{{prefix}}/share'\n";
+    fs::write(entry.join("package.yml"), yaml).unwrap();
+    let mut conn = Connection::open(dir.join("cache/pantry.db")).unwrap();
+    pantry_db::cache(&config, &mut conn).unwrap();
+
+    // installed already so nothing is downloaded
+    let prefix = config.pkgx_dir.join("foo.org/v1.0.0");
+    fs::create_dir_all(prefix.join("bin")).unwrap();
+    let foo = prefix.join("bin/foo");
+    fs::write(&foo, "#!/bin/sh\necho \"$FOO_HOME\"\n").unwrap();
+    fs::set_permissions(&foo, fs::Permissions::from_mode(0o755)).unwrap();
+
+    config
+}
+
+#[tokio::test]
+async fn test_pkgx() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-pkgx-{}", std::process::id()));
+    let config = fixture(&dir);
+    let prefix = config.pkgx_dir.join("foo.org/v1.0.0");
+
+    let mut pkgx = Pkgx::new(config).unwrap();
+    let mut events = pkgx.events();
+    assert_eq!(pkgx.which("foo").unwrap().project, "foo.org");
+    assert_eq!(pkgx.which("bar").unwrap_err().code(), "not-found");
+    assert_eq!(pkgx.pkgreq("foo@1").unwrap().to_string(), "foo.org^1");
+    assert_eq!(pkgx.pkgreq("foo.org").unwrap().project, "foo.org");
+
+    let resolution = pkgx
+        .resolve(&[PackageReq::parse("foo.org").unwrap()])
+        .await
+        .unwrap();
+    assert!(resolution.pending.is_empty());
+    assert!(matches!(events.try_recv(), Ok(Event::Resolving)));
+    assert!(matches!(events.try_recv(), Ok(Event::Cached { pkg }) if pkg.project == "foo.org"));
+    let installations = pkgx.install(&resolution).await.unwrap();
+    let env = pkgx.env(&installations).unwrap();
+    let bin = prefix.join("bin").to_string_lossy().to_string();
+    assert!(env["PATH"].starts_with(&bin));
+
+    let output = pkgx.run("foo", &[], true).await.unwrap();
+    let share = prefix.join("share").to_string_lossy().to_string();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(output.status, 0);
+    assert_eq!(
+        String::from_utf8(output.stdout.unwrap()).unwrap().trim(),
+        share
+    );
+}
+
+#[tokio::test]
+async fn test_run_project() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-run-project-{}", std::process::id()));
+    let config = fixture(&dir);
+    let share = config.pkgx_dir.join("foo.org/v1.0.0/share");
+
+    // its only program
+    let mut pkgx = Pkgx::new(config).unwrap();
+    let output = pkgx.run("foo.org", &[], true).await.unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout.unwrap()).unwrap().trim(),
+        share.to_string_lossy()
+    );
+
+    // which is ambiguous once it provides another
+    let config = fixture(&dir);
+    let yaml = "provides: [bin/foo, bin/foo-bar]
+";
+    fs::write(config.pantry_dir.join("projects/foo.org/package.yml"), yaml).unwrap();
+    let mut conn = Connection::open(dir.join("cache/pantry.db")).unwrap();
+    pantry_db::cache(&config, &mut conn).unwrap();
+    let mut pkgx = Pkgx::new(config).unwrap();
+    let err = pkgx.run("foo.org", &[], true).await.unwrap_err();
+
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(err.code(), "conflict");
+    assert_eq!(
+        err.to_string(),
+        "foo.org provides bin/foo, bin/foo-bar, pick one"
+    );
+}
@@ -0,0 +1,19 @@
+use crate::{config::Config, resolve::resolve, types::PackageReq, Error};
+
+#[tokio::test]
+async fn test_resolve_propagates_errors() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-resolve-{}", std::process::id()));
+    let config = Config {
+        pantry_dir: dir.join("cache/pantry"),
+        // so fetching versions fails rather than finding none
+        dist_url: "not a url".to_string(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    };
+    let rq = PackageReq::parse("foo.org^1").unwrap();
+
+    let err = resolve(vec![rq], &config).await.unwrap_err();
+    assert!(matches!(err, Error::Network { .. }), "{:?}", err);
+}
@@ -0,0 +1,51 @@
+use std::cell::Cell;
+
+use crate::{retry::retry, Error};
+
+// fails the first `failures` attempts with `err`, counting every attempt
+async fn attempts(retries: u32, failures: u32, err: fn() -> Error) -> (u32, Result<(), Error>) {
+    let attempts = Cell::new(0);
+    let rv = retry(retries, || {
+        attempts.set(attempts.get() + 1);
+        let failed = attempts.get() <= failures;
+        async move {
+            if failed {
+                Err(err())
+            } else {
+                Ok(())
+            }
+        }
+    })
+    .await;
+    (attempts.get(), rv)
+}
+
+fn timeout() -> Error {
+    Error::Network {
+        url: "https://dist.pkgx.dev".to_string(),
+        status: None,
+        reason: "timed out".to_string(),
+        transient: true,
+    }
+}
+
+fn not_found() -> Error {
+    Error::NotFound("foo".to_string())
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_retry() {
+    let (n, rv) = attempts(3, 2, timeout).await;
+    assert_eq!(n, 3);
+    assert!(rv.is_ok());
+
+    // gives up after `retries` more attempts
+    let (n, rv) = attempts(3, 10, timeout).await;
+    assert_eq!(n, 4);
+    assert!(rv.unwrap_err().is_transient());
+
+    // only transient errors are retried
+    let (n, rv) = attempts(3, 10, not_found).await;
+    assert_eq!(n, 1);
+    assert_eq!(rv.unwrap_err().code(), "not-found");
+}
@@ -0,0 +1,36 @@
+use crate::run::{from_terminal, run};
+use nix::libc;
+use std::collections::HashMap;
+
+#[test]
+fn test_run() {
+    let env = HashMap::from([("FOO".to_string(), "bar".to_string())]);
+    let sh = |script: &str, capture: bool| {
+        let args = vec!["-c".to_string(), script.to_string()];
+        run("/bin/sh", &args, &env, capture).unwrap()
+    };
+
+    let output = sh("echo $FOO; echo oops >&2", true);
+    assert_eq!(output.status, 0);
+    assert_eq!(output.stdout.as_deref(), Some(&b"bar\n"[..]));
+    assert_eq!(output.stderr.as_deref(), Some(&b"oops\n"[..]));
+
+    let output = sh("exit 3", false);
+    assert_eq!(output.status, 3);
+    assert!(output.stdout.is_none());
+
+    // death by signal is reported like shells do
+    assert_eq!(sh("kill -TERM $$", false).status, 128 + 15);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_from_terminal() {
+    // SI_KERNEL, eg. ^C
+    assert!(from_terminal(libc::SIGINT, 0x80));
+    // SI_USER, eg. `kill -INT`
+    assert!(!from_terminal(libc::SIGINT, 0));
+    // SI_TKILL, SI_QUEUE
+    assert!(!from_terminal(libc::SIGTERM, -6));
+    assert!(!from_terminal(libc::SIGTERM, -1));
+}
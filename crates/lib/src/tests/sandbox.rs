@@ -0,0 +1,125 @@
+use crate::{
+    config::Config,
+    sandbox::{Sandbox, Step},
+    types::{Installation, Package},
+};
+use libsemverator::semver::Semver as Version;
+use std::ffi::CString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn config(dir: &Path) -> Config {
+    Config {
+        pantry_dir: dir.join("cache/pantry"),
+        dist_url: String::new(),
+        pkgx_dir: dir.join("pkgx"),
+        config_dir: dir.join("config"),
+        jobs: 1,
+        retries: 0,
+    }
+}
+
+#[test]
+fn test_new() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-sandbox-{}", std::process::id()));
+    let config = config(&dir);
+    let foo = Installation {
+        path: dir.join("elsewhere/foo.org/v1.0.0"),
+        pkg: Package {
+            project: "foo.org".to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+        },
+    };
+
+    let sandbox = Sandbox::new(std::slice::from_ref(&foo), &config).unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(sandbox.read_only.contains(&PathBuf::from("/usr")));
+    assert!(sandbox.read_only.contains(&config.pkgx_dir));
+    assert!(sandbox.read_only.contains(&foo.path));
+    assert_eq!(sandbox.read_write, vec![std::env::current_dir().unwrap()]);
+    assert!(sandbox.network);
+}
+
+#[test]
+fn test_plan() {
+    let dir = std::env::temp_dir().join(format!("pkgx-test-plan-{}", std::process::id()));
+    let home = dir.join("home");
+    fs::create_dir_all(home.join(".pkgx")).unwrap();
+    fs::write(dir.join("file"), "").unwrap();
+    std::os::unix::fs::symlink(&home, dir.join("link")).unwrap();
+
+    let mut sandbox = Sandbox::new(&[], &config(&dir)).unwrap();
+    let root = dir.join("cache/sandbox");
+    sandbox.read_only = ["home/.pkgx", "link", "missing", "file"]
+        .iter()
+        .map(|path| dir.join(path))
+        .collect();
+    // `--allow`ed, with `~/.pkgx` in it
+    sandbox.read_write = vec![home.clone()];
+    let plan = sandbox.plan();
+
+    sandbox.read_write = vec![PathBuf::from("/")];
+    let everything = sandbox.plan();
+    fs::remove_dir_all(&dir).unwrap();
+
+    let cstring = |path: &Path| CString::new(path.to_str().unwrap()).unwrap();
+    // where `path` is in the sandbox
+    let inside = |path: &Path| cstring(&root.join(path.strip_prefix("/").unwrap()));
+    let mkdir = |path: &Path| Step::Mkdir(inside(path));
+    let bind = |path: &Path, read_only| Step::Bind {
+        src: cstring(path),
+        dst: inside(path),
+        read_only,
+    };
+    // ignoring `/dev`, `/proc` and `/tmp`
+    let system = |path: &Path| {
+        ["/dev", "/proc", "/tmp"]
+            .iter()
+            .any(|x| path == Path::new(x))
+    };
+    let mut expected: Vec<Step> = dir
+        .ancestors()
+        .skip(1)
+        .filter(|path| path.parent().is_some() && !system(path))
+        .map(mkdir)
+        .collect();
+    expected.reverse();
+    expected.extend([
+        mkdir(&dir),
+        // a symlink rather than what it points to
+        Step::Symlink {
+            target: cstring(&home),
+            link: inside(&dir.join("link")),
+        },
+        // files are bound onto files
+        Step::Touch(inside(&dir.join("file"))),
+        bind(&dir.join("file"), true),
+        // the writable parent before the read-only child on top of it
+        mkdir(&home),
+        bind(&home, false),
+        mkdir(&home.join(".pkgx")),
+        bind(&home.join(".pkgx"), true),
+    ]);
+
+    let steps: Vec<Step> = plan
+        .unwrap()
+        .steps
+        .into_iter()
+        .filter(|step| match step {
+            Step::Mkdir(path) | Step::Touch(path) => {
+                let path = Path::new(path.to_str().unwrap())
+                    .strip_prefix(&root)
+                    .unwrap();
+                !system(&Path::new("/").join(path))
+            }
+            Step::Bind { src, .. } => !system(Path::new(src.to_str().unwrap())),
+            Step::Tmpfs(_) => false,
+            Step::Symlink { .. } => true,
+        })
+        .collect();
+    assert_eq!(steps, expected);
+
+    let err = everything.err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
@@ -0,0 +1,65 @@
+use crate::script::{parse_deps, parse_shebang, Shebang};
+
+fn shebang(plus: &[&str], program: &str, args: &[&str]) -> Option<Shebang> {
+    Some(Shebang {
+        plus: plus.iter().map(|x| x.to_string()).collect(),
+        program: program.to_string(),
+        args: args.iter().map(|x| x.to_string()).collect(),
+    })
+}
+
+#[test]
+fn test_parse_shebang() {
+    assert_eq!(parse_shebang("/bin/sh"), shebang(&[], "/bin/sh", &[]));
+    assert_eq!(
+        parse_shebang("/usr/bin/python3 -u"),
+        shebang(&[], "/usr/bin/python3", &["-u"])
+    );
+    assert_eq!(
+        parse_shebang("/usr/bin/env python3"),
+        shebang(&[], "python3", &[])
+    );
+    assert_eq!(
+        parse_shebang("/usr/bin/env -S FOO=bar -u BAZ node --harmony"),
+        shebang(&[], "node", &["--harmony"])
+    );
+    assert_eq!(
+        parse_shebang("/usr/bin/env -S pkgx +jq bash"),
+        shebang(&["jq"], "bash", &[])
+    );
+    assert_eq!(
+        parse_shebang("/usr/bin/env -S pkgx python@3.9"),
+        shebang(&[], "python@3.9", &[])
+    );
+    assert_eq!(
+        parse_shebang("/usr/bin/env -S pkgx --silent +python@3.11 +jq uv run --script"),
+        shebang(&["python@3.11", "jq"], "uv", &["run", "--script"])
+    );
+    assert_eq!(
+        parse_shebang("/usr/local/bin/pkgx deno run"),
+        shebang(&[], "deno", &["run"])
+    );
+    assert_eq!(parse_shebang(""), None);
+    assert_eq!(parse_shebang("/usr/bin/env -S"), None);
+    assert_eq!(parse_shebang("/usr/bin/env -S pkgx +jq"), None);
+}
+
+#[test]
+fn test_parse_deps() {
+    let script = r#"#!/usr/bin/env -S pkgx bash
+# pkgx: +jq +curl.se@8 python@3.12
+
+#pkgx: node^20
+set -e
+# pkgx: not-a-header
+"#;
+    assert_eq!(
+        parse_deps(script.lines()),
+        vec!["jq", "curl.se@8", "python@3.12", "node^20"]
+    );
+
+    let script = "// pkgx: +deno.land\n//\n// comment\nconsole.log(1)\n";
+    assert_eq!(parse_deps(script.lines()), vec!["deno.land"]);
+
+    assert!(parse_deps("echo hi\n# pkgx: jq".lines()).is_empty());
+}
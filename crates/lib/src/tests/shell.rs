@@ -0,0 +1,105 @@
+use crate::shell::{activate, apply, changes, deactivate, diff, render, Change, Shell, DEV};
+use std::collections::HashMap;
+
+fn env(vars: &[(&str, &str)]) -> HashMap<String, String> {
+    vars.iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_activate_deactivate() {
+    let original = env(&[("PATH", "/usr/bin:/bin"), ("SSL_CERT_FILE", "/etc/ssl")]);
+    let changes = vec![
+        (
+            "PATH".to_string(),
+            Change::Prepend(vec!["/pkgx/a/bin".to_string()]),
+        ),
+        (
+            "MANPATH".to_string(),
+            Change::Prepend(vec!["/pkgx/a/man".to_string()]),
+        ),
+        (
+            "SSL_CERT_FILE".to_string(),
+            Change::Set("/pkgx/ca.pem".to_string()),
+        ),
+    ];
+
+    let active = apply(&original, &activate(&original, &changes));
+    assert_eq!(active["PATH"], "/pkgx/a/bin:/usr/bin:/bin");
+    assert_eq!(active["MANPATH"], "/pkgx/a/man");
+    assert_eq!(active["SSL_CERT_FILE"], "/pkgx/ca.pem");
+
+    // activating again replaces rather than stacks
+    let changes = vec![(
+        "PATH".to_string(),
+        Change::Prepend(vec!["/pkgx/b/bin".to_string()]),
+    )];
+    let active = apply(&active, &activate(&active, &changes));
+    assert_eq!(active["PATH"], "/pkgx/b/bin:/usr/bin:/bin");
+    assert!(!active.contains_key("MANPATH"));
+    assert_eq!(active["SSL_CERT_FILE"], "/etc/ssl");
+
+    assert_eq!(apply(&active, &deactivate(&active)), original);
+    assert_eq!(deactivate(&original), vec![(DEV.to_string(), None)]);
+}
+
+#[test]
+fn test_render() {
+    let assignments = vec![
+        ("PATH".to_string(), Some("/a b:/it's".to_string())),
+        ("FOO".to_string(), Some("\"$x\\".to_string())),
+        ("BAR".to_string(), None),
+    ];
+
+    assert_eq!(
+        render(Shell::Bash, &assignments),
+        "export PATH='/a b:/it'\\''s'\nexport FOO='\"$x\\'\nunset BAR\n"
+    );
+    assert_eq!(
+        render(Shell::Fish, &assignments),
+        "set -gx --path PATH '/a b' '/it\\'s'\nset -gx FOO '\"$x\\\\'\nset -e BAR\n"
+    );
+    assert_eq!(
+        render(Shell::Elvish, &assignments),
+        "set-env PATH '/a b:/it''s'\nset-env FOO '\"$x\\'\nunset-env BAR\n"
+    );
+    assert_eq!(
+        render(Shell::Nushell, &assignments),
+        "$env.PATH = [\"/a b\" \"/it's\"]\n$env.FOO = \"\\\"$x\\\\\"\nhide-env -i BAR\n"
+    );
+}
+
+#[test]
+fn test_changes() {
+    let paths = HashMap::from([("PATH".to_string(), vec!["/a".to_string(), "/b".to_string()])]);
+    let runtime = env(&[("PATH", "/a:/b"), ("SSL_CERT_FILE", "/c")]);
+
+    assert_eq!(
+        changes(&paths, runtime),
+        vec![
+            (
+                "PATH".to_string(),
+                Change::Prepend(vec!["/a".to_string(), "/b".to_string()])
+            ),
+            ("SSL_CERT_FILE".to_string(), Change::Set("/c".to_string())),
+        ]
+    );
+}
+
+#[test]
+fn test_diff() {
+    let current = env(&[("FOO", "1"), (DEV, "abc")]);
+    let assignments = vec![
+        ("FOO".to_string(), Some("1".to_string())),
+        ("BAR".to_string(), None),
+        (DEV.to_string(), Some("def".to_string())),
+    ];
+
+    assert_eq!(
+        diff(&current, assignments),
+        vec![(DEV.to_string(), Some("def".to_string()))]
+    );
+    // without the keys the env is gone but the hook must still reactivate
+    assert_eq!(deactivate(&current), vec![(DEV.to_string(), None)]);
+}
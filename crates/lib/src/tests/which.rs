@@ -0,0 +1,33 @@
+use crate::which::rank;
+
+#[test]
+fn test_rank() {
+    let ranked = rank(
+        "yarn",
+        vec!["yarnpkg.com".to_string(), "classic.yarnpkg.com".to_string()],
+    );
+    assert_eq!(ranked, vec!["classic.yarnpkg.com", "yarnpkg.com"]);
+
+    let ranked = rank(
+        "deno",
+        vec![
+            "github.com/denoland/deno".to_string(),
+            "deno.land".to_string(),
+            "example.com".to_string(),
+        ],
+    );
+    assert_eq!(
+        ranked,
+        vec!["deno.land", "github.com/denoland/deno", "example.com"]
+    );
+
+    let ranked = rank(
+        "foo",
+        vec![
+            "github.com/a/b".to_string(),
+            "b.org".to_string(),
+            "a.org".to_string(),
+        ],
+    );
+    assert_eq!(ranked, vec!["a.org", "b.org", "github.com/a/b"]);
+}
@@ -1,9 +1,9 @@
+use crate::Error;
 use lazy_static::lazy_static;
 use libsemverator::range::Range as VersionReq;
 use libsemverator::semver::Semver as Version;
 use serde::ser::SerializeStruct;
 use serde::{Serialize, Serializer};
-use std::error::Error;
 use std::fmt;
 
 //TODO regex is probs not most efficient (but do perf tests if you change it)
@@ -32,11 +32,11 @@ pub struct PackageReq {
 use regex::Regex;
 
 impl PackageReq {
-    pub fn parse(pkgspec: &str) -> Result<Self, Box<dyn Error>> {
+    pub fn parse(pkgspec: &str) -> Result<Self, Error> {
         let input = pkgspec.trim();
         let captures = PACKAGE_REGEX
             .captures(input)
-            .ok_or_else(|| format!("invalid pkgspec: {}", input))?;
+            .ok_or_else(|| Error::Invalid(format!("invalid pkgspec: {}", input)))?;
 
         let project = captures.get(1).unwrap().as_str().to_string();
         let str = if let Some(cap) = captures.get(2) {
@@ -44,7 +44,8 @@ impl PackageReq {
         } else {
             "*"
         };
-        let constraint = VersionReq::parse(str)?;
+        let constraint = VersionReq::parse(str)
+            .map_err(|err| Error::Invalid(format!("invalid pkgspec: {}: {}", input, err)))?;
 
         Ok(Self {
             project,
@@ -0,0 +1,48 @@
+use crate::Error;
+use crate::{config::Config, env::EnvRule};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// The user’s `config.yml`, stored in `Config::config_dir`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct UserConfig {
+    /// preferred project for programs provided by multiple projects
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub programs: BTreeMap<String, String>,
+
+    /// extra directories to add to environment variables, see `env::rules`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, EnvRule>,
+
+    // keys we don’t know about are preserved when we save
+    #[serde(flatten)]
+    other: BTreeMap<String, serde_yaml::Value>,
+}
+
+impl UserConfig {
+    pub fn load(config: &Config) -> Result<Self, Error> {
+        let path = path(config);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        if content.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_yaml::from_str(&content)
+            .map_err(|e| Error::Invalid(format!("invalid config: {}: {}", path.display(), e)))
+    }
+
+    pub fn save(&self, config: &Config) -> Result<(), Error> {
+        fs::create_dir_all(&config.config_dir)?;
+        let content = serde_yaml::to_string(self).map_err(|e| Error::Invalid(e.to_string()))?;
+        fs::write(path(config), content)?;
+        Ok(())
+    }
+}
+
+fn path(config: &Config) -> PathBuf {
+    config.config_dir.join("config.yml")
+}
@@ -1,6 +1,9 @@
-use std::{error::Error, os::unix::fs::PermissionsExt, path::Path};
+use crate::Error;
+use std::{os::unix::fs::PermissionsExt, path::Path};
 
-pub async fn find_program(arg: &str, paths: &Vec<String>) -> Result<String, Box<dyn Error>> {
+use crate::{pantry_db::Provider, types::Installation};
+
+pub async fn find_program(arg: &str, paths: &Vec<String>) -> Result<String, Error> {
     if arg.starts_with("/") {
         return Ok(arg.to_string());
     } else if arg.contains("/") {
@@ -19,5 +22,19 @@ pub async fn find_program(arg: &str, paths: &Vec<String>) -> Result<String, Box<
             }
         }
     }
-    Err(format!("cmd not found: {}", arg).into())
+    Err(Error::NotFound(arg.to_string()))
+}
+
+/// The provider’s program within its installation, if it is there and executable.
+pub fn provided_program(provider: &Provider, installations: &[Installation]) -> Option<String> {
+    let installation = installations
+        .iter()
+        .find(|installation| installation.pkg.project == provider.project)?;
+    let full_path = installation.path.join(&provider.path);
+    let metadata = full_path.metadata().ok()?;
+    if full_path.is_file() && (metadata.permissions().mode() & 0o111 != 0) {
+        Some(full_path.to_str()?.to_string())
+    } else {
+        None
+    }
 }
@@ -0,0 +1,154 @@
+use crate::{
+    pantry_db::{self, Interpreter, Provider},
+    user_config::UserConfig,
+};
+use rusqlite::Connection;
+
+#[derive(Debug)]
+pub enum WhichError {
+    CmdNotFound(String),
+    MultipleProjects(String, Vec<String>),
+    DbError(rusqlite::Error),
+}
+
+impl std::fmt::Display for WhichError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WhichError::CmdNotFound(cmd) => write!(f, "cmd not found: {}", cmd),
+            WhichError::MultipleProjects(cmd, projects) => {
+                write!(f, "multiple projects found for {}: {:?}", cmd, projects)
+            }
+            WhichError::DbError(err) => write!(f, "db error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for WhichError {}
+
+/// Finds the project that provides `cmd` and where in that project it lives.
+///
+/// If several projects provide `cmd` we pick, in order:
+///   1. the only candidate the user explicitly `requested` (eg. via `+pkg`)
+///   2. the user’s preference from `config.yml`
+///   3. whatever `disambiguate` returns given the `rank`ed candidates
+///
+/// Front-ends that cannot ask the user can pass `|_, ranked| ranked.first().cloned()`.
+pub fn which<F>(
+    cmd: &str,
+    requested: &[String],
+    conn: &Connection,
+    user_config: &UserConfig,
+    disambiguate: F,
+) -> Result<Provider, WhichError>
+where
+    F: FnOnce(&str, &[String]) -> Option<String>,
+{
+    let mut providers = pantry_db::which(&cmd.to_string(), conn).map_err(WhichError::DbError)?;
+
+    // a project may provide `cmd` more than once (eg. `bin/foo` and `sbin/foo`)
+    // the first is best since exact matches precede versioned ones
+    let mut candidates: Vec<String> = vec![];
+    providers.retain(|provider| {
+        if candidates.contains(&provider.project) {
+            false
+        } else {
+            candidates.push(provider.project.clone());
+            true
+        }
+    });
+
+    let project = if candidates.len() == 1 {
+        candidates.remove(0)
+    } else if candidates.is_empty() {
+        return Err(WhichError::CmdNotFound(cmd.to_string()));
+    } else {
+        choose(cmd, candidates, requested, user_config, disambiguate)?
+    };
+
+    Ok(providers
+        .into_iter()
+        .find(|provider| provider.project == project)
+        .unwrap())
+}
+
+/// Finds the project that runs scripts with this `extension`, eg. `py`.
+///
+/// Disambiguates like `which`, preferences are keyed by the extension with
+/// its leading dot, eg. `.js: deno.land`.
+pub fn interpreter<F>(
+    extension: &str,
+    requested: &[String],
+    conn: &Connection,
+    user_config: &UserConfig,
+    disambiguate: F,
+) -> Result<Interpreter, WhichError>
+where
+    F: FnOnce(&str, &[String]) -> Option<String>,
+{
+    let key = format!(".{}", extension);
+    let mut interpreters = pantry_db::interpreters(extension, conn).map_err(WhichError::DbError)?;
+    let mut candidates: Vec<String> = interpreters
+        .iter()
+        .map(|interpreter| interpreter.project.clone())
+        .collect();
+
+    let project = if candidates.len() == 1 {
+        candidates.remove(0)
+    } else if candidates.is_empty() {
+        return Err(WhichError::CmdNotFound(key));
+    } else {
+        choose(&key, candidates, requested, user_config, disambiguate)?
+    };
+
+    let index = interpreters
+        .iter()
+        .position(|interpreter| interpreter.project == project)
+        .unwrap();
+    Ok(interpreters.swap_remove(index))
+}
+
+fn choose<F>(
+    cmd: &str,
+    candidates: Vec<String>,
+    requested: &[String],
+    user_config: &UserConfig,
+    disambiguate: F,
+) -> Result<String, WhichError>
+where
+    F: FnOnce(&str, &[String]) -> Option<String>,
+{
+    let explicit: Vec<&String> = candidates
+        .iter()
+        .filter(|project| requested.contains(project))
+        .collect();
+    if explicit.len() == 1 {
+        return Ok(explicit[0].clone());
+    }
+
+    if let Some(preferred) = user_config.programs.get(cmd) {
+        if candidates.contains(preferred) {
+            return Ok(preferred.clone());
+        }
+    }
+
+    let ranked = rank(cmd, candidates);
+
+    match disambiguate(cmd, &ranked) {
+        Some(project) if ranked.contains(&project) => Ok(project),
+        _ => Err(WhichError::MultipleProjects(cmd.to_string(), ranked)),
+    }
+}
+
+/// Deterministically orders projects that all provide `cmd`:
+///   1. projects named for the program (eg. `deno` → `deno.land`) come first
+///   2. then shallower names (`foo.org` before `github.com/foo/bar`)
+///   3. then alphabetically
+pub fn rank(cmd: &str, mut candidates: Vec<String>) -> Vec<String> {
+    candidates.sort_by_key(|project| {
+        let named_for = project.split(['.', '/']).any(|part| part == cmd);
+        let depth = project.matches('/').count();
+        (!named_for, depth, project.clone())
+    });
+    candidates.dedup();
+    candidates
+}